@@ -1,19 +1,41 @@
 use std::collections::HashMap;
 
-use nom::{character::complete::multispace0, multi::many0, sequence::delimited, IResult};
+use nom::{sequence::preceded, IResult};
 
-use crate::identifier_parser::{parse_identifier, Identifier};
+use crate::{
+    comment_parser::whitespace0,
+    identifier_parser::Identifier,
+    statement_parser::{parse_statements, statement_identifiers, Statement},
+    types::TypeAlias,
+};
 
-pub fn make_ast(input: &str) -> IResult<&str, HashMap<String, Identifier>> {
-    let (remainder, identifiers) =
-        many0(delimited(multispace0, parse_identifier, multispace0))(input)?;
-    let mut ast = HashMap::new();
-    for ident in identifiers {
-        // if let Some(ident) = ident_option {
-        //     ast.insert(ident.name.clone(), ident);
-        // }
-        ast.insert(ident.name.clone(), ident);
+#[derive(Debug, PartialEq, Clone)]
+pub struct Ast {
+    pub identifiers: HashMap<String, Identifier>,
+    pub statements: Vec<Statement>,
+}
+
+impl Ast {
+    /// The types declared with `export type`, which other modules can use.
+    pub fn exported_types(&self) -> Vec<&TypeAlias> {
+        self.statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::TypeAlias(alias) if alias.exported => Some(alias),
+                _ => None,
+            })
+            .collect()
     }
+}
+
+pub fn make_ast(input: &str) -> IResult<&str, Ast> {
+    let (remainder, statements) = preceded(whitespace0, parse_statements)(input)?;
 
-    Ok((remainder, ast))
+    Ok((
+        remainder,
+        Ast {
+            identifiers: statement_identifiers(&statements),
+            statements,
+        },
+    ))
 }
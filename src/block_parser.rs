@@ -1,112 +1,224 @@
-// parses any type of blocks (if, do, while, repeat)
+// parses any type of blocks (if, do, while, repeat, for)
 
 use std::collections::HashMap;
 
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_until},
-    character::{
-        complete::{alphanumeric1, multispace0},
-        streaming::space1,
-    },
-    combinator::{map, recognize},
-    multi::{many0, many1},
-    sequence::{delimited, pair, terminated, tuple},
+    bytes::complete::tag,
+    combinator::{map, opt},
+    multi::{many0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
 };
 
-use crate::identifier_parser::{parse_identifier, Identifier, IdentifierValues};
+use crate::{
+    comment_parser::whitespace0,
+    expression_parser::{parse_expression, parse_expression_list, Expression},
+    identifier_parser::{expression_value, keyword, parse_equals, Identifier, IdentifierValues},
+    statement_parser::{
+        parse_local_variable, parse_statements, statement_identifiers, LocalVariable, Statement,
+    },
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum BlockType {
     Do,
-    If,
-    While,
-    Repeat,
+    /// The block holds the statements of the `then` branch.
+    If {
+        condition: Box<Expression>,
+        else_ifs: Vec<Block>,
+        else_block: Option<Box<Block>>,
+    },
+    ElseIf(Box<Expression>),
+    Else,
+    While(Box<Expression>),
+    Repeat(Box<Expression>),
+    NumericFor {
+        variable: LocalVariable,
+        start: Box<Expression>,
+        limit: Box<Expression>,
+        step: Option<Box<Expression>>,
+    },
+    GenericFor {
+        variables: Vec<LocalVariable>,
+        expressions: Vec<Expression>,
+    },
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Block {
     pub block_type: BlockType,
     pub identifiers: HashMap<String, Identifier>,
+    pub statements: Vec<Statement>,
 }
 
-fn parse_repeat(input: &str) -> IResult<&str, Block> {
-    let (remainder, _repeat_tag) = delimited(multispace0, tag("repeat"), multispace0)(input)?;
-    let (more_remainder, (bytes, _last)) = pair(
-        take_until("until"),
-        terminated(many1(alt((alphanumeric1, space1, tag("_")))), multispace0),
-    )(remainder)?;
-    let (_, identifiers) = many0(parse_identifier)(bytes)?;
-
-    let mut block = Block {
-        block_type: BlockType::Repeat,
-        identifiers: HashMap::new(),
-    };
-
-    for ident in identifiers {
-        block.identifiers.insert(ident.name.clone(), ident);
+fn make_block(block_type: BlockType, statements: Vec<Statement>) -> Block {
+    Block {
+        block_type,
+        identifiers: statement_identifiers(&statements),
+        statements,
     }
-
-    Ok((more_remainder, block))
 }
 
-fn parse_while(input: &str) -> IResult<&str, &str> {
-    let (remainder, _while_tag) = delimited(multispace0, tag("while"), multispace0)(input)?;
-    let (more_remainder, bytes) = take_until("do")(remainder)?;
-    Ok((more_remainder, bytes))
+fn parse_repeat(input: &str) -> IResult<&str, Block> {
+    let (remainder, (_, statements, _, condition, _)) = tuple((
+        delimited(whitespace0, keyword("repeat"), whitespace0),
+        parse_statements,
+        pair(keyword("until"), whitespace0),
+        parse_expression,
+        whitespace0,
+    ))(input)?;
+
+    Ok((
+        remainder,
+        make_block(BlockType::Repeat(Box::new(condition)), statements),
+    ))
 }
 
-fn parse_end(input: &str) -> IResult<&str, (&str, &str)> {
-    tuple((take_until("end"), terminated(tag("end"), multispace0)))(input)
+fn parse_while(input: &str) -> IResult<&str, Expression> {
+    delimited(
+        pair(keyword("while"), whitespace0),
+        parse_expression,
+        whitespace0,
+    )(input)
 }
 
 fn parse_do(input: &str) -> IResult<&str, Block> {
-    let (remainder, _) = recognize(pair(tag("do"), multispace0))(input)?;
-    let (remainder_2, (bytes, _end)) = parse_end(remainder)?;
-    let (_, identifiers) = many0(parse_identifier)(bytes)?;
+    let (remainder, statements) = delimited(
+        pair(keyword("do"), whitespace0),
+        parse_statements,
+        pair(keyword("end"), whitespace0),
+    )(input)?;
 
-    let mut block = Block {
-        block_type: BlockType::Do,
-        identifiers: HashMap::new(),
-    };
+    Ok((remainder, make_block(BlockType::Do, statements)))
+}
 
-    for ident in identifiers {
-        if ident.value != IdentifierValues::End {
-            block.identifiers.insert(ident.name.clone(), ident);
-        }
-    }
+/// Parses `<condition> then <statements>`, the part shared by `if` and `elseif`.
+fn parse_condition_branch(input: &str) -> IResult<&str, (Expression, Vec<Statement>)> {
+    let (remainder, (condition, _, statements)) = tuple((
+        parse_expression,
+        delimited(whitespace0, keyword("then"), whitespace0),
+        parse_statements,
+    ))(input)?;
 
-    Ok((remainder_2, block))
+    Ok((remainder, (condition, statements)))
 }
 
 fn parse_if(input: &str) -> IResult<&str, Block> {
-    let (remainder, _if_tag) = delimited(multispace0, tag("if"), multispace0)(input)?;
-    let (remainder_2, _rest_of_line) =
-        pair(take_until("then"), terminated(tag("then"), multispace0))(remainder)?;
-
-    let (real_remainder, (bytes, _end_tag)) = parse_end(remainder_2)?;
-    let (_, identifiers) = many0(parse_identifier)(bytes)?;
-    let mut block = Block {
-        block_type: BlockType::If,
-        identifiers: HashMap::new(),
+    let (remainder, (_, (condition, statements), else_ifs, else_block, _)) = tuple((
+        delimited(whitespace0, keyword("if"), whitespace0),
+        parse_condition_branch,
+        many0(map(
+            preceded(pair(keyword("elseif"), whitespace0), parse_condition_branch),
+            |(condition, statements)| {
+                make_block(BlockType::ElseIf(Box::new(condition)), statements)
+            },
+        )),
+        opt(map(
+            preceded(pair(keyword("else"), whitespace0), parse_statements),
+            |statements| Box::new(make_block(BlockType::Else, statements)),
+        )),
+        terminated(keyword("end"), whitespace0),
+    ))(input)?;
+
+    let block_type = BlockType::If {
+        condition: Box::new(condition),
+        else_ifs,
+        else_block,
+    };
+
+    Ok((remainder, make_block(block_type, statements)))
+}
+
+fn parse_numeric_for(input: &str) -> IResult<&str, BlockType> {
+    let (remainder, (_, variable, _, start, _, limit, step, _)) = tuple((
+        delimited(whitespace0, keyword("for"), whitespace0),
+        parse_local_variable,
+        parse_equals,
+        parse_expression,
+        tuple((whitespace0, tag(","), whitespace0)),
+        parse_expression,
+        opt(preceded(
+            tuple((whitespace0, tag(","), whitespace0)),
+            parse_expression,
+        )),
+        whitespace0,
+    ))(input)?;
+
+    Ok((
+        remainder,
+        BlockType::NumericFor {
+            variable,
+            start: Box::new(start),
+            limit: Box::new(limit),
+            step: step.map(Box::new),
+        },
+    ))
+}
+
+fn parse_generic_for(input: &str) -> IResult<&str, BlockType> {
+    let (remainder, (_, variables, _, expressions, _)) = tuple((
+        delimited(whitespace0, keyword("for"), whitespace0),
+        separated_list1(
+            tuple((whitespace0, tag(","), whitespace0)),
+            parse_local_variable,
+        ),
+        delimited(whitespace0, keyword("in"), whitespace0),
+        parse_expression_list,
+        whitespace0,
+    ))(input)?;
+
+    Ok((
+        remainder,
+        BlockType::GenericFor {
+            variables,
+            expressions,
+        },
+    ))
+}
+
+/// Loop variables are locals of the loop body, unless the body shadows them. A
+/// numeric loop variable starts at the `start` value, while generic loop
+/// variables are only known at runtime.
+fn parse_for(input: &str) -> IResult<&str, Block> {
+    let (remainder, (block_type, mut block)) =
+        pair(alt((parse_numeric_for, parse_generic_for)), parse_do)(input)?;
+
+    let variables = match &block_type {
+        BlockType::NumericFor {
+            variable, start, ..
+        } => vec![(variable.clone(), expression_value(*start.clone()))],
+        BlockType::GenericFor { variables, .. } => variables
+            .iter()
+            .map(|variable| (variable.clone(), IdentifierValues::Unknown))
+            .collect(),
+        _ => Vec::new(),
     };
 
-    for ident in identifiers {
-        block.identifiers.insert(ident.name.clone(), ident);
+    for (variable, value) in variables {
+        block
+            .identifiers
+            .entry(variable.name.clone())
+            .or_insert(Identifier {
+                name: variable.name,
+                value,
+                type_annotation: variable.type_annotation,
+            });
     }
+    block.block_type = block_type;
 
-    Ok((real_remainder, block))
+    Ok((remainder, block))
 }
 
 pub fn parse_block(input: &str) -> IResult<&str, Block> {
     alt((
-        map(pair(parse_while, parse_do), |(_, mut b)| {
-            b.block_type = BlockType::While;
+        map(pair(parse_while, parse_do), |(condition, mut b)| {
+            b.block_type = BlockType::While(Box::new(condition));
             b
         }),
-        map(parse_do, |b| b),
-        map(parse_repeat, |b| b),
-        map(parse_if, |b| b),
+        parse_do,
+        parse_repeat,
+        parse_if,
+        parse_for,
     ))(input)
 }
@@ -1,19 +1,43 @@
 use nom::{
-    bytes::complete::{tag, take, take_until},
-    sequence::{preceded, tuple},
+    branch::alt,
+    bytes::complete::{tag, take_till},
+    character::complete::{line_ending, multispace1},
+    combinator::{map, opt, recognize, verify},
+    multi::many0_count,
+    sequence::{preceded, terminated},
     IResult,
 };
 
-pub fn parse_comment_line(input: &str) -> IResult<&str, &str> {
-    let (remainder, (comment_line, _)) =
-        preceded(tag("--"), tuple((take_until("\n"), take(1u16))))(input)?;
+use crate::datatype_parsers::string_parser::parse_long_bracket;
 
-    Ok((remainder, comment_line))
+/// Parses a comment that runs to the end of the line, or of the input when it
+/// is on the last line.
+pub fn parse_comment_line(input: &str) -> IResult<&str, &str> {
+    terminated(
+        preceded(tag("--"), take_till(|c| c == '\n' || c == '\r')),
+        opt(line_ending),
+    )(input)
 }
 
+/// Parses a `--[[ ]]` comment, at any level such as `--[==[ ]==]`.
 pub fn parse_comment_block(input: &str) -> IResult<&str, &str> {
-    let (remainder, (comment_line, _)) =
-        preceded(tag("--[["), tuple((take_until("]]"), take(2u16))))(input)?;
+    map(preceded(tag("--"), parse_long_bracket), |(_, contents)| {
+        contents
+    })(input)
+}
+
+/// `--[` that doesn't open a long bracket starts a line comment instead.
+pub fn parse_comment(input: &str) -> IResult<&str, &str> {
+    alt((parse_comment_block, parse_comment_line))(input)
+}
+
+/// Skips any whitespace and comments. Use this wherever a Luau program may
+/// have whitespace.
+pub fn whitespace0(input: &str) -> IResult<&str, &str> {
+    recognize(many0_count(alt((multispace1, parse_comment))))(input)
+}
 
-    Ok((remainder, comment_line))
+/// Like `whitespace0`, but needs at least one space or comment.
+pub fn whitespace1(input: &str) -> IResult<&str, &str> {
+    verify(whitespace0, |skipped: &str| !skipped.is_empty())(input)
 }
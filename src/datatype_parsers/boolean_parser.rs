@@ -1,8 +1,8 @@
-use nom::{branch::alt, bytes::complete::tag_no_case, combinator::map, IResult};
+use nom::{branch::alt, combinator::value, IResult};
 
+use crate::identifier_parser::keyword;
+
+/// Parses `true` or `false`. Booleans are keywords, so `True` is a name.
 pub fn parse_boolean(input: &str) -> IResult<&str, bool> {
-    alt((
-        map(tag_no_case("true"), |_| true),
-        map(tag_no_case("false"), |_| false),
-    ))(input)
+    alt((value(true, keyword("true")), value(false, keyword("false"))))(input)
 }
@@ -1,36 +1,113 @@
 use nom::{
     branch::alt,
-    character::complete::{char, one_of},
-    combinator::{map, opt, recognize},
+    character::complete::{char, one_of, satisfy},
+    combinator::{map, not, opt, recognize},
     multi::{many0, many1},
     sequence::tuple,
     sequence::{preceded, terminated},
     IResult,
 };
 
+/// A number literal as written in the source. `integer` is set when the
+/// literal is an integer that fits in 64 bits, which Lua 5.3+ keeps apart from
+/// floats. Hexadecimal and binary literals wrap around like they do in Lua.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NumberLiteral {
+    pub text: String,
+    pub value: f64,
+    pub integer: Option<i64>,
+}
+
+fn digits<'a>(allowed: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    recognize(many1(terminated(one_of(allowed), many0(char('_')))))
+}
+
 fn decimal(input: &str) -> IResult<&str, &str> {
-    recognize(many1(terminated(one_of("0123456789"), many0(char('_')))))(input)
+    digits("0123456789")(input)
+}
+
+/// Parses the digits after a `0x` or `0b` prefix, which may start with an
+/// underscore as in `0x_FF`.
+fn prefixed<'a>(
+    prefix: &'static str,
+    allowed: &'static str,
+) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    recognize(tuple((
+        char('0'),
+        one_of(prefix),
+        many0(char('_')),
+        digits(allowed),
+    )))
+}
+
+/// Builds the literal for a hexadecimal or binary number.
+fn radix_literal(text: &str, radix: u32) -> NumberLiteral {
+    let (value, integer) = text[2..].chars().filter_map(|c| c.to_digit(radix)).fold(
+        (0f64, 0u64),
+        |(value, integer), digit| {
+            (
+                value * radix as f64 + digit as f64,
+                integer
+                    .wrapping_mul(radix as u64)
+                    .wrapping_add(digit as u64),
+            )
+        },
+    );
+
+    NumberLiteral {
+        text: text.to_string(),
+        value,
+        integer: Some(integer as i64),
+    }
+}
+
+/// Builds the literal for a decimal number. Integers too large for 64 bits
+/// become floats, as they do in Lua.
+fn decimal_literal(text: &str) -> NumberLiteral {
+    let cleaned = text.replace('_', "");
+    let integer = if cleaned.contains(['.', 'e', 'E']) {
+        None
+    } else {
+        cleaned.parse::<i64>().ok()
+    };
+
+    NumberLiteral {
+        text: text.to_string(),
+        // digits with an optional dot and exponent are always a valid float
+        value: cleaned.parse::<f64>().unwrap_or_default(),
+        integer,
+    }
 }
 
-pub fn parse_number(input: &str) -> IResult<&str, f32> {
-    map(
+pub fn parse_number(input: &str) -> IResult<&str, NumberLiteral> {
+    terminated(
         alt((
-            // Case one: .42
-            recognize(tuple((
-                char('.'),
-                decimal,
-                opt(tuple((one_of("eE"), opt(one_of("+-")), decimal))),
-            ))), // Case two: 42e42 and 42.42e42
-            recognize(tuple((
-                decimal,
-                opt(preceded(char('.'), decimal)),
-                one_of("eE"),
-                opt(one_of("+-")),
-                decimal,
-            ))), // Case three: 42. and 42.42
-            recognize(tuple((decimal, char('.'), opt(decimal)))),
-            recognize(decimal),
+            map(prefixed("xX", "0123456789abcdefABCDEF"), |text| {
+                radix_literal(text, 16)
+            }),
+            map(prefixed("bB", "01"), |text| radix_literal(text, 2)),
+            map(
+                alt((
+                    // Case one: .42
+                    recognize(tuple((
+                        char('.'),
+                        decimal,
+                        opt(tuple((one_of("eE"), opt(one_of("+-")), decimal))),
+                    ))), // Case two: 42e42 and 42.42e42
+                    recognize(tuple((
+                        decimal,
+                        opt(preceded(char('.'), decimal)),
+                        one_of("eE"),
+                        opt(one_of("+-")),
+                        decimal,
+                    ))), // Case three: 42. and 42.42
+                    recognize(tuple((decimal, char('.'), opt(decimal)))),
+                    recognize(decimal),
+                )),
+                decimal_literal,
+            ),
         )),
-        |number| (number.parse::<f32>().unwrap()),
+        // `0xFG` and `12abc` are malformed numbers, not a number and a name
+        not(satisfy(|c: char| c.is_ascii_alphanumeric() || c == '_')),
     )(input)
 }
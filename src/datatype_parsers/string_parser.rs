@@ -6,128 +6,225 @@
 
 use nom::{
     branch::alt,
-    bytes::complete::{is_not, take_while_m_n},
-    character::complete::{char, multispace1},
-    combinator::{map, map_opt, map_res, value, verify},
-    error::{FromExternalError, ParseError},
-    multi::fold_many0,
-    sequence::{delimited, preceded},
+    bytes::complete::{is_not, tag, take_until, take_while_m_n},
+    character::complete::{char, hex_digit1, multispace0},
+    combinator::{map, map_res, value, verify},
+    error::{ErrorKind, FromExternalError, ParseError},
+    multi::{fold_many0, many0_count},
+    sequence::{delimited, pair, preceded},
     IResult,
 };
 
-/// Parse a unicode sequence, of the form u{XXXX}, where XXXX is 1 to 6
-/// hexadecimal numerals. We will combine this later with parse_escaped_char
-/// to parse sequences like \u{00AC}.
-fn parse_unicode<'a, E>(input: &'a str) -> IResult<&'a str, char, E>
+use crate::{
+    comment_parser::whitespace0,
+    expression_parser::{parse_expression, Expression},
+};
+
+/// The delimiter a string was written with, so it can be written back the same
+/// way. Long brackets store their level, the number of `=` signs in `[==[`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringDelimiter {
+    DoubleQuote,
+    SingleQuote,
+    LongBracket(usize),
+}
+
+/// A string as its decoded bytes. Lua strings are bytes, so escapes like
+/// `\xFF` may make `value` invalid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringLiteral {
+    pub value: Vec<u8>,
+    pub delimiter: StringDelimiter,
+}
+
+/// A part of an interpolated string: either text, or an expression in braces.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterpolatedSegment {
+    Literal(Vec<u8>),
+    Expression(Expression),
+}
+
+/// Parse a unicode sequence, of the form u{XXXX}, where XXXX is one or more
+/// hexadecimal numerals naming a code point no larger than 10FFFF. We will
+/// combine this later with parse_escape to parse sequences like \u{00AC}.
+fn parse_unicode<'a, E>(input: &'a str) -> IResult<&'a str, u32, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
-    // `take_while_m_n` parses between `m` and `n` bytes (inclusive) that match
-    // a predicate. `parse_hex` here parses between 1 and 6 hexadecimal numerals.
-    let parse_hex = take_while_m_n(1, 6, |c: char| c.is_ascii_hexdigit());
-
     // `preceded` takes a prefix parser, and if it succeeds, returns the result
     // of the body parser. In this case, it parses u{XXXX}.
     let parse_delimited_hex = preceded(
         char('u'),
         // `delimited` is like `preceded`, but it parses both a prefix and a suffix.
         // It returns the result of the middle parser. In this case, it parses
-        // {XXXX}, where XXXX is 1 to 6 hex numerals, and returns XXXX
-        delimited(char('{'), parse_hex, char('}')),
+        // {XXXX}, where XXXX is any number of hex numerals, and returns XXXX
+        delimited(char('{'), hex_digit1, char('}')),
     );
 
     // `map_res` takes the result of a parser and applies a function that returns
-    // a Result. In this case we take the hex bytes from parse_hex and attempt to
-    // convert them to a u32.
+    // a Result. In this case we take the hex bytes and attempt to convert them
+    // to a u32, which fails when there are too many digits.
     let parse_u32 = map_res(parse_delimited_hex, move |hex| u32::from_str_radix(hex, 16));
 
-    // map_opt is like map_res, but it takes an Option instead of a Result. If
-    // the function returns None, map_opt returns an error. In this case, because
-    // not all u32 values are valid unicode code points, we have to fallibly
-    // convert to char with from_u32.
-    map_opt(parse_u32, |value| std::char::from_u32(value))(input)
+    // Luau rejects code points past 10FFFF, but not surrogates, which aren't
+    // valid chars and are encoded by `push_code_point` instead.
+    verify(parse_u32, |code_point| *code_point <= 0x10FFFF)(input)
 }
 
-/// Parse an escaped character: \n, \t, \r, \u{00AC}, etc.
-fn parse_escaped_char<'a, E>(input: &'a str) -> IResult<&'a str, char, E>
+/// Parse a byte written as exactly two hexadecimal numerals: \xXX
+fn parse_hex_byte<'a, E>(input: &'a str) -> IResult<&'a str, u8, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
     preceded(
-        char('\\'),
-        // `alt` tries each parser in sequence, returning the result of
-        // the first successful match
-        alt((
-            parse_unicode,
-            // The `value` parser returns a fixed value (the first argument) if its
-            // parser (the second argument) succeeds. In these cases, it looks for
-            // the marker characters (n, r, t, etc) and returns the matching
-            // character (\n, \r, \t, etc).
-            value('\n', char('n')),
-            value('\r', char('r')),
-            value('\t', char('t')),
-            value('\u{08}', char('b')),
-            value('\u{0C}', char('f')),
-            value('\\', char('\\')),
-            value('/', char('/')),
-            value('"', char('"')),
-        )),
+        char('x'),
+        map_res(
+            take_while_m_n(2, 2, |c: char| c.is_ascii_hexdigit()),
+            |hex| u8::from_str_radix(hex, 16),
+        ),
+    )(input)
+}
+
+/// Parse a byte written as up to three decimal numerals, no larger than 255:
+/// \ddd
+fn parse_decimal_byte<'a, E>(input: &'a str) -> IResult<&'a str, u8, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+{
+    map_res(
+        take_while_m_n(1, 3, |c: char| c.is_ascii_digit()),
+        |digits: &str| digits.parse::<u8>(),
     )(input)
 }
 
-// fn string(input: &str) -> IResult<&str, Vec<&str>> {
-//     delimited(char('"'), many1(alt((alpha1, multispace1))), char('"'))(input)
-// }
+/// Parse an escape sequence: \n, \t, \', \x41, \65, \u{00AC}, \z, etc. A
+/// backslash followed by anything that isn't a Lua escape is a failure, rather
+/// than an error, so the whole string is reported as invalid instead of being
+/// silently skipped.
+fn parse_escape<'a, E>(input: &'a str) -> IResult<&'a str, StringFragment<'a>, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+{
+    let (after_backslash, _) = char('\\')(input)?;
 
-/// Parse a backslash, followed by any amount of whitespace. This is used later
-/// to discard any escaped whitespace.
-fn parse_escaped_whitespace<'a, E: ParseError<&'a str>>(
-    input: &'a str,
-) -> IResult<&'a str, &'a str, E> {
-    preceded(char('\\'), multispace1)(input)
+    // `alt` tries each parser in sequence, returning the result of
+    // the first successful match
+    let escape = alt((
+        map(parse_unicode::<E>, StringFragment::EscapedCodePoint),
+        map(parse_hex_byte::<E>, StringFragment::EscapedByte),
+        map(parse_decimal_byte::<E>, StringFragment::EscapedByte),
+        // \z skips the whitespace that follows it, including newlines
+        value(StringFragment::EscapedWS, pair(char('z'), multispace0)),
+        // a backslash before a line break keeps the line break
+        value(
+            StringFragment::EscapedChar('\n'),
+            alt((tag("\r\n"), tag("\n"), tag("\r"))),
+        ),
+        // The `value` parser returns a fixed value (the first argument) if its
+        // parser (the second argument) succeeds. In these cases, it looks for
+        // the marker characters (n, r, t, etc) and returns the matching
+        // character (\n, \r, \t, etc).
+        map(
+            alt((
+                value('\u{07}', char('a')),
+                value('\u{08}', char('b')),
+                value('\u{0C}', char('f')),
+                value('\n', char('n')),
+                value('\r', char('r')),
+                value('\t', char('t')),
+                value('\u{0B}', char('v')),
+                value('\\', char('\\')),
+                value('"', char('"')),
+                value('\'', char('\'')),
+            )),
+            StringFragment::EscapedChar,
+        ),
+    ))(after_backslash);
+
+    escape.map_err(|_| nom::Err::Failure(E::from_error_kind(input, ErrorKind::Escaped)))
 }
 
-/// Parse a non-empty block of text that doesn't include \ or "
-fn parse_literal<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+/// Parse a non-empty block of text that doesn't include \ or the closing quote
+fn parse_literal<'a, E: ParseError<&'a str>>(
+    quote: char,
+) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str, E> {
     // `is_not` parses a string of 0 or more characters that aren't one of the
     // given characters.
-    let not_quote_slash = is_not("\"\\");
+    let not_quote_slash = is_not(if quote == '"' { "\"\\" } else { "'\\" });
 
     // `verify` runs a parser, then runs a verification function on the output of
     // the parser. The verification function accepts out output only if it
     // returns true. In this case, we want to ensure that the output of is_not
     // is non-empty.
-    verify(not_quote_slash, |s: &str| !s.is_empty())(input)
+    verify(not_quote_slash, |s: &str| !s.is_empty())
 }
 
 /// A string fragment contains a fragment of a string being parsed: either
 /// a non-empty Literal (a series of non-escaped characters), a single
-/// parsed escaped character, or a block of escaped whitespace.
+/// parsed escaped character, code point or byte, or a block of escaped
+/// whitespace.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum StringFragment<'a> {
     Literal(&'a str),
     EscapedChar(char),
+    EscapedCodePoint(u32),
+    EscapedByte(u8),
     EscapedWS,
 }
 
-/// Combine parse_literal, parse_escaped_whitespace, and parse_escaped_char
-/// into a StringFragment.
-fn parse_fragment<'a, E>(input: &'a str) -> IResult<&'a str, StringFragment<'a>, E>
+/// Append the UTF-8 encoding of a code point, which may be a surrogate that
+/// `char` can't hold, as Luau does for \u{D800}.
+fn push_code_point(bytes: &mut Vec<u8>, code_point: u32) {
+    let continuation = |shift: u32| 0x80 | ((code_point >> shift) & 0x3F) as u8;
+    match code_point {
+        0..=0x7F => bytes.push(code_point as u8),
+        0x80..=0x7FF => bytes.extend([0xC0 | (code_point >> 6) as u8, continuation(0)]),
+        0x800..=0xFFFF => bytes.extend([
+            0xE0 | (code_point >> 12) as u8,
+            continuation(6),
+            continuation(0),
+        ]),
+        _ => bytes.extend([
+            0xF0 | (code_point >> 18) as u8,
+            continuation(12),
+            continuation(6),
+            continuation(0),
+        ]),
+    }
+}
+
+/// Append a fragment to the bytes of a string. Lua strings are bytes, so
+/// escapes like \xFF may produce invalid UTF-8.
+fn push_fragment(bytes: &mut Vec<u8>, fragment: StringFragment) {
+    match fragment {
+        StringFragment::Literal(s) => bytes.extend_from_slice(s.as_bytes()),
+        StringFragment::EscapedChar(c) => {
+            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes())
+        }
+        StringFragment::EscapedCodePoint(code_point) => push_code_point(bytes, code_point),
+        StringFragment::EscapedByte(byte) => bytes.push(byte),
+        StringFragment::EscapedWS => {}
+    }
+}
+
+/// Combine parse_literal and parse_escape into a StringFragment.
+fn parse_fragment<'a, E>(
+    quote: char,
+) -> impl FnMut(&'a str) -> IResult<&'a str, StringFragment<'a>, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
     alt((
         // The `map` combinator runs a parser, then applies a function to the output
         // of that parser.
-        map(parse_literal, StringFragment::Literal),
-        map(parse_escaped_char, StringFragment::EscapedChar),
-        value(StringFragment::EscapedWS, parse_escaped_whitespace),
-    ))(input)
+        map(parse_literal(quote), StringFragment::Literal),
+        parse_escape,
+    ))
 }
 
-/// Parse a string. Use a loop of parse_fragment and push all of the fragments
-/// into an output string.
-pub fn parse_string<'a, E>(input: &'a str) -> IResult<&'a str, String, E>
+/// Parse a string between two `quote` characters. Use a loop of parse_fragment
+/// and push all of the fragments into the bytes of the string.
+fn parse_quoted<'a, E>(quote: char) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<u8>, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
@@ -135,24 +232,116 @@ where
     // and for each output value, calls a folding function on each output value.
     let build_string = fold_many0(
         // Our parser function– parses a single string fragment
-        parse_fragment,
+        parse_fragment(quote),
         // Our init value, an empty string
-        String::new,
+        Vec::new,
         // Our folding function. For each fragment, append the fragment to the
         // string.
-        |mut string, fragment| {
-            match fragment {
-                StringFragment::Literal(s) => string.push_str(s),
-                StringFragment::EscapedChar(c) => string.push(c),
-                StringFragment::EscapedWS => {}
-            }
-            string
+        |mut bytes, fragment| {
+            push_fragment(&mut bytes, fragment);
+            bytes
         },
     );
 
     // Finally, parse the string. Note that, if `build_string` could accept a raw
-    // " character, the closing delimiter " would never match. When using
+    // quote character, the closing delimiter would never match. When using
     // `delimited` with a looping parser (like fold_many0), be sure that the
     // loop won't accidentally match your closing delimiter!
-    delimited(char('"'), build_string, char('"'))(input)
+    delimited(char(quote), build_string, char(quote))
+}
+
+/// Parse the contents of a long bracket, `[[ ... ]]` or `[==[ ... ]==]`, along
+/// with its level. Nothing inside is escaped, and a newline directly after the
+/// opening bracket is not part of the contents.
+pub fn parse_long_bracket<'a, E: ParseError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, (usize, &'a str), E> {
+    let (remainder, level) = delimited(char('['), many0_count(char('=')), char('['))(input)?;
+
+    let close = format!("]{}]", "=".repeat(level));
+    let (remainder, contents) = take_until(close.as_str())(remainder)?;
+    let (remainder, _) = tag(close.as_str())(remainder)?;
+
+    let contents = contents
+        .strip_prefix("\r\n")
+        .or_else(|| contents.strip_prefix('\n'))
+        .unwrap_or(contents);
+
+    Ok((remainder, (level, contents)))
+}
+
+/// Parse a string in any of its forms: "double quoted", 'single quoted' or
+/// between long brackets.
+pub fn parse_string<'a, E>(input: &'a str) -> IResult<&'a str, StringLiteral, E>
+where
+    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+{
+    alt((
+        map(parse_quoted('"'), |value| StringLiteral {
+            value,
+            delimiter: StringDelimiter::DoubleQuote,
+        }),
+        map(parse_quoted('\''), |value| StringLiteral {
+            value,
+            delimiter: StringDelimiter::SingleQuote,
+        }),
+        map(parse_long_bracket, |(level, value)| StringLiteral {
+            value: value.as_bytes().to_vec(),
+            delimiter: StringDelimiter::LongBracket(level),
+        }),
+    ))(input)
+}
+
+/// Parse a fragment of an interpolated string. Besides the usual escapes,
+/// \` and \{ stand for a literal backtick and brace.
+fn parse_interpolated_fragment(input: &str) -> IResult<&str, StringFragment<'_>> {
+    alt((
+        map(
+            verify(is_not("`\\{"), |s: &str| !s.is_empty()),
+            StringFragment::Literal,
+        ),
+        map(
+            preceded(char('\\'), alt((char('`'), char('{')))),
+            StringFragment::EscapedChar,
+        ),
+        parse_escape,
+    ))(input)
+}
+
+/// Parse the text between two expressions of an interpolated string.
+fn parse_interpolated_literal(input: &str) -> IResult<&str, Vec<u8>> {
+    fold_many0(
+        parse_interpolated_fragment,
+        Vec::new,
+        |mut bytes, fragment| {
+            push_fragment(&mut bytes, fragment);
+            bytes
+        },
+    )(input)
+}
+
+/// Parse a string interpolation such as `Hello {name}!` into its literal and
+/// expression segments, in order. Empty literals are left out.
+pub fn parse_interpolated_string(input: &str) -> IResult<&str, Vec<InterpolatedSegment>> {
+    let (mut remainder, _) = char('`')(input)?;
+    let mut segments = Vec::new();
+
+    loop {
+        let (after_literal, literal) = parse_interpolated_literal(remainder)?;
+        if !literal.is_empty() {
+            segments.push(InterpolatedSegment::Literal(literal));
+        }
+
+        if let Some(after_string) = after_literal.strip_prefix('`') {
+            return Ok((after_string, segments));
+        }
+
+        let (after_expression, expression) = delimited(
+            pair(char('{'), whitespace0),
+            parse_expression,
+            pair(whitespace0, char('}')),
+        )(after_literal)?;
+        segments.push(InterpolatedSegment::Expression(expression));
+        remainder = after_expression;
+    }
 }
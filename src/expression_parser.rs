@@ -0,0 +1,369 @@
+// parses expressions, following the operator precedence and associativity of Luau
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{char, one_of},
+    combinator::{cut, map, map_opt, not, opt, value},
+    multi::{fold_many0, many0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+use crate::{
+    comment_parser::whitespace0,
+    datatype_parsers::string_parser::{
+        parse_interpolated_string, parse_string, InterpolatedSegment,
+    },
+    identifier_parser::{keyword, parse_literal, parse_name, IdentifierValues},
+    table_parser::parse_table,
+    types::{parse_type, Types},
+};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    FloorDivide,
+    Modulo,
+    Power,
+    Concat,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    And,
+    Or,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+    Length,
+}
+
+/// A call of `function`, or of `function:method` when `method` is set.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Call {
+    pub function: Box<Expression>,
+    pub method: Option<String>,
+    pub arguments: Vec<Expression>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expression {
+    Value(IdentifierValues),
+    Name(String),
+    /// `...`, the extra arguments of a vararg function or chunk.
+    Varargs,
+    Parentheses(Box<Expression>),
+    Field {
+        table: Box<Expression>,
+        name: String,
+    },
+    /// `table[key]`
+    Index {
+        table: Box<Expression>,
+        key: Box<Expression>,
+    },
+    Call(Call),
+    InterpolatedString(Vec<InterpolatedSegment>),
+    Binary {
+        left: Box<Expression>,
+        operator: BinaryOperator,
+        right: Box<Expression>,
+    },
+    Unary {
+        operator: UnaryOperator,
+        operand: Box<Expression>,
+    },
+    /// `if condition then a elseif other then b else c`. Unlike the statement,
+    /// the `else` branch is required.
+    If {
+        condition: Box<Expression>,
+        then_value: Box<Expression>,
+        else_ifs: Vec<(Expression, Expression)>,
+        else_value: Box<Expression>,
+    },
+    /// `expression :: Type`
+    TypeAssertion {
+        expression: Box<Expression>,
+        asserted_type: Types,
+    },
+}
+
+/// Unary operators bind tighter than every binary operator except `^`.
+const UNARY_PRIORITY: u8 = 12;
+
+impl BinaryOperator {
+    /// The left and right priority of the operator. A right priority lower
+    /// than the left one makes the operator right associative.
+    fn priority(self) -> (u8, u8) {
+        match self {
+            Self::Or => (1, 1),
+            Self::And => (2, 2),
+            Self::Equal
+            | Self::NotEqual
+            | Self::LessThan
+            | Self::LessThanOrEqual
+            | Self::GreaterThan
+            | Self::GreaterThanOrEqual => (3, 3),
+            Self::Concat => (9, 8),
+            Self::Add | Self::Subtract => (10, 10),
+            Self::Multiply | Self::Divide | Self::FloorDivide | Self::Modulo => (11, 11),
+            Self::Power => (14, 13),
+        }
+    }
+}
+
+fn parse_binary_operator(input: &str) -> IResult<&str, BinaryOperator> {
+    alt((
+        value(BinaryOperator::FloorDivide, tag("//")),
+        value(
+            BinaryOperator::Concat,
+            terminated(tag(".."), not(char('.'))),
+        ),
+        value(BinaryOperator::Equal, tag("==")),
+        value(BinaryOperator::NotEqual, tag("~=")),
+        value(BinaryOperator::LessThanOrEqual, tag("<=")),
+        value(BinaryOperator::GreaterThanOrEqual, tag(">=")),
+        value(BinaryOperator::Add, tag("+")),
+        // `--` starts a comment, not a subtraction
+        value(
+            BinaryOperator::Subtract,
+            terminated(tag("-"), not(char('-'))),
+        ),
+        value(BinaryOperator::Multiply, tag("*")),
+        value(BinaryOperator::Divide, tag("/")),
+        value(BinaryOperator::Modulo, tag("%")),
+        value(BinaryOperator::Power, tag("^")),
+        value(BinaryOperator::LessThan, tag("<")),
+        value(BinaryOperator::GreaterThan, tag(">")),
+        value(BinaryOperator::And, keyword("and")),
+        value(BinaryOperator::Or, keyword("or")),
+    ))(input)
+}
+
+fn parse_unary_operator(input: &str) -> IResult<&str, UnaryOperator> {
+    alt((
+        value(UnaryOperator::Not, keyword("not")),
+        value(UnaryOperator::Length, tag("#")),
+        value(UnaryOperator::Negate, terminated(tag("-"), not(char('-')))),
+    ))(input)
+}
+
+/// Something that can follow a prefix expression.
+enum Suffix {
+    Field(String),
+    Index(Expression),
+    Call(Option<String>, Vec<Expression>),
+}
+
+/// Parses the arguments of a call: `(a, b)`, `{...}` or `"string"`.
+fn parse_call_arguments(input: &str) -> IResult<&str, Vec<Expression>> {
+    alt((
+        delimited(
+            pair(char('('), whitespace0),
+            map(opt(parse_expression_list), Option::unwrap_or_default),
+            pair(whitespace0, char(')')),
+        ),
+        map(parse_table, |table| {
+            vec![Expression::Value(IdentifierValues::Table(table))]
+        }),
+        map(parse_string, |string| {
+            vec![Expression::Value(IdentifierValues::String(string))]
+        }),
+    ))(input)
+}
+
+/// Parses `[expression]`, where `[` doesn't start a long bracket string like
+/// `[[text]]`.
+pub fn parse_index(input: &str) -> IResult<&str, Expression> {
+    delimited(
+        pair(terminated(char('['), not(one_of("[="))), whitespace0),
+        parse_expression,
+        pair(whitespace0, char(']')),
+    )(input)
+}
+
+fn parse_suffix(input: &str) -> IResult<&str, Suffix> {
+    preceded(
+        whitespace0,
+        alt((
+            map(preceded(pair(char('.'), whitespace0), parse_name), |name| {
+                Suffix::Field(name.to_string())
+            }),
+            map(parse_index, Suffix::Index),
+            map(
+                pair(
+                    preceded(pair(char(':'), whitespace0), parse_name),
+                    preceded(whitespace0, parse_call_arguments),
+                ),
+                |(method, arguments)| Suffix::Call(Some(method.to_string()), arguments),
+            ),
+            map(parse_call_arguments, |arguments| {
+                Suffix::Call(None, arguments)
+            }),
+        )),
+    )(input)
+}
+
+/// Parses a name or parenthesized expression, followed by any number of
+/// `.name` and `[key]` accesses and calls.
+pub fn parse_prefix_expression(input: &str) -> IResult<&str, Expression> {
+    let (remainder, prefix) = alt((
+        map(
+            delimited(
+                pair(char('('), whitespace0),
+                parse_expression,
+                pair(whitespace0, char(')')),
+            ),
+            |expression| Expression::Parentheses(Box::new(expression)),
+        ),
+        map(parse_name, |name| Expression::Name(name.to_string())),
+    ))(input)?;
+
+    fold_many0(
+        parse_suffix,
+        move || prefix.clone(),
+        |expression, suffix| match suffix {
+            Suffix::Field(name) => Expression::Field {
+                table: Box::new(expression),
+                name,
+            },
+            Suffix::Index(key) => Expression::Index {
+                table: Box::new(expression),
+                key: Box::new(key),
+            },
+            Suffix::Call(method, arguments) => Expression::Call(Call {
+                function: Box::new(expression),
+                method,
+                arguments,
+            }),
+        },
+    )(remainder)
+}
+
+pub fn parse_call(input: &str) -> IResult<&str, Call> {
+    map_opt(parse_prefix_expression, |expression| match expression {
+        Expression::Call(call) => Some(call),
+        _ => None,
+    })(input)
+}
+
+/// Parses `condition then value`, the part shared by `if` and `elseif`.
+fn parse_if_branch(input: &str) -> IResult<&str, (Expression, Expression)> {
+    pair(
+        parse_expression,
+        preceded(
+            tuple((whitespace0, keyword("then"), whitespace0)),
+            parse_expression,
+        ),
+    )(input)
+}
+
+fn parse_if_expression(input: &str) -> IResult<&str, Expression> {
+    let (remainder, ((condition, then_value), else_ifs, else_value)) = tuple((
+        preceded(pair(keyword("if"), whitespace0), parse_if_branch),
+        many0(preceded(
+            tuple((whitespace0, keyword("elseif"), whitespace0)),
+            parse_if_branch,
+        )),
+        // there is nothing else an `if` could be inside an expression
+        cut(preceded(
+            tuple((whitespace0, keyword("else"), whitespace0)),
+            parse_expression,
+        )),
+    ))(input)?;
+
+    Ok((
+        remainder,
+        Expression::If {
+            condition: Box::new(condition),
+            then_value: Box::new(then_value),
+            else_ifs,
+            else_value: Box::new(else_value),
+        },
+    ))
+}
+
+fn parse_simple_expression(input: &str) -> IResult<&str, Expression> {
+    alt((
+        value(Expression::Varargs, tag("...")),
+        map(parse_literal, Expression::Value),
+        map(parse_interpolated_string, Expression::InterpolatedString),
+        parse_if_expression,
+        parse_prefix_expression,
+    ))(input)
+}
+
+/// Parses a simple expression followed by any number of `:: Type` assertions,
+/// which bind tighter than every operator.
+fn parse_assertion_expression(input: &str) -> IResult<&str, Expression> {
+    let (remainder, expression) = parse_simple_expression(input)?;
+
+    fold_many0(
+        preceded(tuple((whitespace0, tag("::"), whitespace0)), parse_type),
+        move || expression.clone(),
+        |expression, asserted_type| Expression::TypeAssertion {
+            expression: Box::new(expression),
+            asserted_type,
+        },
+    )(remainder)
+}
+
+/// Parses an expression whose binary operators all have a left priority
+/// greater than `limit`.
+fn parse_sub_expression(input: &str, limit: u8) -> IResult<&str, Expression> {
+    let (mut remainder, mut expression) = match parse_unary_operator(input) {
+        Ok((after_operator, operator)) => {
+            let (remainder, operand) =
+                preceded(whitespace0, |i| parse_sub_expression(i, UNARY_PRIORITY))(after_operator)?;
+            (
+                remainder,
+                Expression::Unary {
+                    operator,
+                    operand: Box::new(operand),
+                },
+            )
+        }
+        Err(_) => parse_assertion_expression(input)?,
+    };
+
+    while let Ok((after_operator, operator)) =
+        preceded(whitespace0, parse_binary_operator)(remainder)
+    {
+        let (left_priority, right_priority) = operator.priority();
+        if left_priority <= limit {
+            break;
+        }
+
+        let (after_right, right) =
+            preceded(whitespace0, |i| parse_sub_expression(i, right_priority))(after_operator)?;
+        expression = Expression::Binary {
+            left: Box::new(expression),
+            operator,
+            right: Box::new(right),
+        };
+        remainder = after_right;
+    }
+
+    Ok((remainder, expression))
+}
+
+pub fn parse_expression(input: &str) -> IResult<&str, Expression> {
+    parse_sub_expression(input, 0)
+}
+
+/// Parses one or more comma separated expressions.
+pub fn parse_expression_list(input: &str) -> IResult<&str, Vec<Expression>> {
+    separated_list1(
+        tuple((whitespace0, char(','), whitespace0)),
+        parse_expression,
+    )(input)
+}
@@ -1,18 +1,28 @@
-use std::{collections::HashMap, str::FromStr};
+use std::collections::HashMap;
 
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take, take_until},
-    character::complete::{alpha1, alphanumeric1, multispace0},
-    combinator::{map, opt, recognize},
-    multi::{many0, many0_count},
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{map, opt, verify},
+    error::{Error, ErrorKind},
+    multi::{separated_list0, separated_list1},
     sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
 };
 
 use crate::{
-    identifier_parser::{parse_identifier, Identifier, IdentifierValues},
-    types::Types,
+    block_parser::{Block, BlockType},
+    comment_parser::{whitespace0, whitespace1},
+    datatype_parsers::string_parser::InterpolatedSegment,
+    expression_parser::{Call, Expression},
+    identifier_parser::{keyword, parse_name, Identifier, IdentifierValues},
+    statement_parser::{parse_statements, statement_identifiers, Statement},
+    table_parser::{Table, TableKey, TableMemberType},
+    types::{
+        parse_annotation, parse_generic_parameters, parse_return_annotation, GenericParameter,
+        TypePack, Types,
+    },
 };
 
 #[derive(Debug, PartialEq, Clone)]
@@ -24,117 +34,286 @@ pub struct FunctionArguments {
 #[derive(PartialEq, Debug, Clone)]
 pub struct Function {
     pub name: String,
-    pub return_type: Types,
+    /// The tables the function is stored in, outermost first. Empty unless the
+    /// function was declared as `function a.b.name()` or `function a.b:name()`.
+    pub path: Vec<String>,
+    /// Declared with `:`, so the function has an implicit `self` parameter.
+    pub is_method: bool,
+    /// The generic parameters, as in `function map<T, U>()`.
+    pub generics: Vec<GenericParameter>,
+    /// The annotated return types, if any.
+    pub returns: Option<TypePack>,
     pub arguments: Vec<FunctionArguments>,
+    /// The type of `...` when the function takes varargs.
+    pub varargs: Option<Types>,
     pub identifiers: HashMap<String, Identifier>,
+    pub statements: Vec<Statement>,
 }
 
-fn parse_arguments(args: Vec<String>) -> Vec<FunctionArguments> {
-    let mut function_arguments = Vec::new();
-
-    for arg in args {
-        if arg.contains(":") {
-            let split: Vec<String> = arg.split(":").map(|s| s.to_string()).collect();
-            let arg_name = split.get(0).unwrap().trim().to_string();
-            let arg_type = split.get(1).unwrap();
-            let trimmed_type = arg_type.trim().to_lowercase();
-            let function_argument = Types::from_str(&trimmed_type);
-
-            match function_argument {
-                Ok(func_type) => function_arguments.push(FunctionArguments {
-                    name: arg_name,
-                    function_type: func_type,
-                }),
-                Err(_) => function_arguments.push(FunctionArguments {
-                    name: arg_name,
-                    function_type: Types::Any,
-                }),
-            };
-        } else {
-            function_arguments.push(FunctionArguments {
-                name: arg.trim().to_string(),
-                function_type: Types::Any,
-            });
+impl Function {
+    /// The name as written in the declaration, such as `a.b:name`.
+    pub fn full_name(&self) -> String {
+        let mut full_name = String::new();
+        for table in &self.path {
+            full_name.push_str(table);
+            full_name.push('.');
         }
+        if self.is_method {
+            full_name.pop();
+            full_name.push(':');
+        }
+        full_name.push_str(&self.name);
+
+        full_name
     }
+}
+
+fn parse_argument(input: &str) -> IResult<&str, FunctionArguments> {
+    let (remainder, (name, function_type)) = pair(parse_name, opt(parse_annotation))(input)?;
 
-    return function_arguments;
+    Ok((
+        remainder,
+        FunctionArguments {
+            name: name.to_string(),
+            function_type: function_type.unwrap_or(Types::Any),
+        },
+    ))
 }
 
-fn argument_parser(input: &str) -> IResult<&str, &str> {
-    alt((alphanumeric1, tag("_"), tag(": "), tag(":")))(input)
+/// Parses `...` or `...: type` at the end of the arguments.
+fn parse_varargs(input: &str) -> IResult<&str, Types> {
+    map(
+        preceded(tag("..."), opt(parse_annotation)),
+        |varargs_type| varargs_type.unwrap_or(Types::Any),
+    )(input)
 }
 
-fn argument_possibilities(input: &str) -> IResult<&str, Vec<String>> {
-    many0(alt((
-        terminated(
-            delimited(
-                multispace0,
-                map(many0(argument_parser), |s: Vec<&str>| {
-                    s.iter().map(|e| e.to_string()).collect::<String>()
-                }),
-                multispace0,
-            ),
-            tag(","),
-        ),
-        terminated(
-            delimited(
-                multispace0,
-                map(many0(argument_parser), |s: Vec<&str>| {
-                    s.iter().map(|e| e.to_string()).collect::<String>()
-                }),
-                multispace0,
+fn parse_arguments(input: &str) -> IResult<&str, (Vec<FunctionArguments>, Option<Types>)> {
+    let separator = || tuple((whitespace0, char(','), whitespace0));
+
+    delimited(
+        pair(char('('), whitespace0),
+        alt((
+            map(parse_varargs, |varargs| (Vec::new(), Some(varargs))),
+            pair(
+                separated_list0(separator(), parse_argument),
+                opt(preceded(separator(), parse_varargs)),
             ),
-            tag(")"),
-        ),
-    )))(input)
+        )),
+        pair(whitespace0, char(')')),
+    )(input)
+}
+
+/// Parses the generics, arguments and return type shared by every kind of
+/// function.
+fn parse_signature(input: &str) -> IResult<&str, Function> {
+    let (remainder, (generics, (arguments, varargs), returns, _end_of_line)) = tuple((
+        opt(terminated(parse_generic_parameters, whitespace0)),
+        parse_arguments,
+        opt(parse_return_annotation),
+        whitespace0,
+    ))(input)?;
+
+    Ok((
+        remainder,
+        Function {
+            name: String::new(),
+            path: Vec::new(),
+            is_method: false,
+            generics: generics.unwrap_or_default(),
+            returns,
+            arguments,
+            varargs,
+            identifiers: HashMap::new(),
+            statements: Vec::new(),
+        },
+    ))
 }
 
 pub fn parse_function_definition(input: &str) -> IResult<&str, Function> {
-    let (remainder, ((_, _), identifier_name, args, return_type, _end_of_line)) = tuple((
-        pair(tag("function"), tag(" ")),
-        recognize(pair(
-            alt((alpha1, tag("_"))),
-            many0_count(alt((alphanumeric1, tag("_")))),
-        )),
-        preceded(tag("("), argument_possibilities),
-        opt(tuple((alt((tag(": "), tag(":"))), alpha1))),
-        opt(multispace0),
+    let (remainder, (_, (mut path, method), mut func)) = tuple((
+        pair(keyword("function"), whitespace1),
+        pair(
+            separated_list1(char('.'), parse_name),
+            opt(preceded(char(':'), parse_name)),
+        ),
+        parse_signature,
     ))(input)?;
 
-    let mut func = Function {
-        name: identifier_name.to_string(),
-        return_type: Types::Any,
-        arguments: parse_arguments(args),
-        identifiers: HashMap::new(),
+    let name = match method {
+        Some(method) => method,
+        None => path.pop().unwrap_or_default(),
     };
 
-    println!("{} {:?}", remainder, return_type);
-    match return_type {
-        Some((_, s)) => {
-            func.return_type = match Types::from_str(s) {
-                Ok(t) => t,
-                Err(_) => Types::Any,
-            };
+    func.name = name.to_string();
+    func.path = path.into_iter().map(|table| table.to_string()).collect();
+    func.is_method = method.is_some();
+
+    Ok((remainder, func))
+}
+
+fn table_uses_varargs(table: &Table) -> bool {
+    table.members.iter().any(|member| {
+        matches!(&member.key, TableKey::Expression(key) if expression_uses_varargs(key))
+            || match &member.is_a {
+                TableMemberType::RawType(value) => value_uses_varargs(value),
+                TableMemberType::NestedTable(nested) => table_uses_varargs(nested),
+                TableMemberType::Function(_) | TableMemberType::Method(_) => false,
+            }
+    })
+}
+
+fn value_uses_varargs(value: &IdentifierValues) -> bool {
+    match value {
+        IdentifierValues::Table(table) => table_uses_varargs(table),
+        IdentifierValues::Expression(expression) => expression_uses_varargs(expression),
+        _ => false,
+    }
+}
+
+/// Whether `...` is used by the expression. A function inside the expression
+/// has its own varargs, so it isn't searched.
+fn expression_uses_varargs(expression: &Expression) -> bool {
+    match expression {
+        Expression::Varargs => true,
+        Expression::Value(value) => value_uses_varargs(value),
+        Expression::Name(_) => false,
+        Expression::Parentheses(inner) => expression_uses_varargs(inner),
+        Expression::Field { table, .. } => expression_uses_varargs(table),
+        Expression::Index { table, key } => {
+            expression_uses_varargs(table) || expression_uses_varargs(key)
         }
-        None => {}
+        Expression::Call(call) => call_uses_varargs(call),
+        Expression::InterpolatedString(segments) => segments.iter().any(|segment| {
+            matches!(segment, InterpolatedSegment::Expression(inner) if expression_uses_varargs(inner))
+        }),
+        Expression::Binary { left, right, .. } => {
+            expression_uses_varargs(left) || expression_uses_varargs(right)
+        }
+        Expression::Unary { operand, .. } => expression_uses_varargs(operand),
+        Expression::TypeAssertion { expression, .. } => expression_uses_varargs(expression),
+        Expression::If {
+            condition,
+            then_value,
+            else_ifs,
+            else_value,
+        } => {
+            expression_uses_varargs(condition)
+                || expression_uses_varargs(then_value)
+                || else_ifs.iter().any(|(condition, value)| {
+                    expression_uses_varargs(condition) || expression_uses_varargs(value)
+                })
+                || expression_uses_varargs(else_value)
+        }
+    }
+}
+
+fn call_uses_varargs(call: &Call) -> bool {
+    expression_uses_varargs(&call.function) || call.arguments.iter().any(expression_uses_varargs)
+}
+
+fn block_uses_varargs(block: &Block) -> bool {
+    let header = match &block.block_type {
+        BlockType::If {
+            condition,
+            else_ifs,
+            else_block,
+        } => {
+            expression_uses_varargs(condition)
+                || else_ifs.iter().any(block_uses_varargs)
+                || else_block.as_deref().is_some_and(block_uses_varargs)
+        }
+        BlockType::ElseIf(condition)
+        | BlockType::While(condition)
+        | BlockType::Repeat(condition) => expression_uses_varargs(condition),
+        BlockType::NumericFor {
+            start, limit, step, ..
+        } => {
+            expression_uses_varargs(start)
+                || expression_uses_varargs(limit)
+                || step.as_deref().is_some_and(expression_uses_varargs)
+        }
+        BlockType::GenericFor { expressions, .. } => {
+            expressions.iter().any(expression_uses_varargs)
+        }
+        BlockType::Do | BlockType::Else => false,
     };
 
-    Ok((remainder, func))
+    header || statements_use_varargs(&block.statements)
 }
 
-pub fn parse_function(input: &str) -> IResult<&str, Function> {
-    let (remainder, mut function) = parse_function_definition(input)?;
-    let (remainder_2, (bytes, _end)) = tuple((take_until("end"), take(3 as u16)))(remainder)?;
-    let (_, identifiers) = many0(parse_identifier)(bytes)?;
-
-    for identifier in identifiers {
-        if identifier.value != IdentifierValues::End {
-            function
-                .identifiers
-                .insert(identifier.name.clone(), identifier);
+fn statements_use_varargs(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::Local(local) => local.values.iter().any(expression_uses_varargs),
+        Statement::Assignment(assignment) => assignment
+            .targets
+            .iter()
+            .chain(&assignment.values)
+            .any(expression_uses_varargs),
+        Statement::CompoundAssignment(assignment) => {
+            expression_uses_varargs(&assignment.target)
+                || expression_uses_varargs(&assignment.value)
         }
+        Statement::Call(call) => call_uses_varargs(call),
+        Statement::Block(block) => block_uses_varargs(block),
+        Statement::Return(values) => values.iter().any(expression_uses_varargs),
+        Statement::Function(_)
+        | Statement::LocalFunction(_)
+        | Statement::Break
+        | Statement::TypeAlias(_) => false,
+    })
+}
+
+/// Parses the statements of a function up to and including its `end`. Using
+/// `...` in a function that doesn't take varargs is a failure.
+fn parse_function_body(input: &str, mut function: Function) -> IResult<&str, Function> {
+    let (remainder, statements) = terminated(parse_statements, keyword("end"))(input)?;
+    if function.varargs.is_none() && statements_use_varargs(&statements) {
+        return Err(nom::Err::Failure(Error::new(input, ErrorKind::Verify)));
     }
+    function.identifiers = statement_identifiers(&statements);
+    function.statements = statements;
+
+    Ok((remainder, function))
+}
+
+pub fn parse_function(input: &str) -> IResult<&str, Function> {
+    let (remainder, function) = parse_function_definition(input)?;
+    parse_function_body(remainder, function)
+}
+
+/// Parses a function expression such as `function(a, b) end`. The function is
+/// unnamed until it is assigned.
+pub fn parse_anonymous_function(input: &str) -> IResult<&str, Function> {
+    let (remainder, function) =
+        preceded(pair(keyword("function"), whitespace0), parse_signature)(input)?;
+    parse_function_body(remainder, function)
+}
+
+/// Parses `local function name() end`. Unlike `local name = function() end`,
+/// the name is in scope inside the body, so it is recorded in the function's
+/// identifiers with the function's signature.
+pub fn parse_local_function(input: &str) -> IResult<&str, Function> {
+    let (remainder, mut function) = preceded(
+        pair(keyword("local"), whitespace1),
+        verify(parse_function, |function| {
+            function.path.is_empty() && !function.is_method
+        }),
+    )(input)?;
+
+    let signature = Function {
+        identifiers: HashMap::new(),
+        statements: Vec::new(),
+        ..function.clone()
+    };
+    function
+        .identifiers
+        .entry(function.name.clone())
+        .or_insert(Identifier {
+            name: function.name.clone(),
+            value: IdentifierValues::Function(Box::new(signature)),
+            type_annotation: None,
+        });
 
-    Ok((remainder_2, function))
+    Ok((remainder, function))
 }
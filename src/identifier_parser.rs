@@ -1,130 +1,160 @@
 use crate::{
-    datatype_parsers::{boolean_parser::parse_boolean, number_parser::parse_number},
-    function_parser::{parse_function, Function},
+    comment_parser::{whitespace0, whitespace1},
+    datatype_parsers::{
+        boolean_parser::parse_boolean,
+        number_parser::{parse_number, NumberLiteral},
+    },
+    expression_parser::{parse_expression, Expression},
+    function_parser::{parse_anonymous_function, Function},
     table_parser::{parse_table, Table},
+    types::{parse_annotation, Types},
 };
 
-use super::datatype_parsers::string_parser::parse_string;
+use super::datatype_parsers::string_parser::{parse_string, StringLiteral};
 
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{alpha1, alphanumeric1, multispace0},
-    combinator::{map, not, opt, recognize},
+    character::complete::{alpha1, alphanumeric1, satisfy},
+    combinator::{map, not, opt, recognize, verify},
+    error::{Error, ErrorKind},
     multi::many0_count,
-    sequence::{pair, tuple},
+    sequence::{pair, terminated, tuple},
     IResult,
 };
 
+/// Reserved words of Luau, which can never be used as a name.
+const KEYWORDS: [&str; 21] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in", "local",
+    "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum IdentifierValues {
-    Number(f32),
-    String(String),
+    Number(NumberLiteral),
+    String(StringLiteral),
     Bool(bool),
     Table(Table),
-    Function(Function),
+    Function(Box<Function>),
+    Expression(Box<Expression>),
     End,
     If,
     Then,
     While,
     Nil,
     Do,
-    Return(String),
+    /// A value that is only known at runtime, such as the extra results of a
+    /// call or the variables of a generic `for` loop.
+    Unknown,
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Identifier {
     pub name: String,
     pub value: IdentifierValues,
+    /// The declared type, as in `local count: number = 0`. This can differ
+    /// from the kind of `value`.
+    pub type_annotation: Option<Types>,
 }
 
-pub fn parse_equals(input: &str) -> IResult<&str, &str> {
-    recognize(many0_count(alt((tag(" "), tag("=")))))(input)
+/// Matches `word` only when it is not the prefix of a longer name, so `and`
+/// does not match the start of `android`.
+pub fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    terminated(
+        tag(word),
+        not(satisfy(|c: char| c.is_ascii_alphanumeric() || c == '_')),
+    )
 }
 
-pub fn parse_return(input: &str) -> IResult<&str, &str> {
-    recognize(pair(
-        tuple((tag("return"), multispace0)),
-        recognize(pair(
-            alt((alpha1, tag("_"))),
-            many0_count(alt((alphanumeric1, tag("_")))),
-        )),
-    ))(input)
+pub fn parse_equals(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((
+        whitespace0,
+        terminated(tag("="), not(tag("="))),
+        whitespace0,
+    )))(input)
 }
 
 pub fn parse_name(input: &str) -> IResult<&str, &str> {
-    recognize(pair(
-        not(tag("function")),
+    verify(
         recognize(pair(
             alt((alpha1, tag("_"))),
             many0_count(alt((alphanumeric1, tag("_")))),
         )),
-    ))(input)
+        |name: &str| !KEYWORDS.contains(&name),
+    )(input)
 }
 
 pub fn parse_local(input: &str) -> IResult<&str, (&str, &str)> {
-    pair(tag("local"), tag(" "))(input)
+    pair(keyword("local"), whitespace1)(input)
 }
 
-fn identifier_name(input: &str) -> IResult<&str, &str> {
-    let (remainder, (identifier_name, _)) = tuple((parse_name, parse_equals))(input)?;
+fn identifier_name(input: &str) -> IResult<&str, (&str, Option<Types>)> {
+    let (remainder, (identifier_name, type_annotation, _)) =
+        tuple((parse_name, opt(parse_annotation), parse_equals))(input)?;
 
-    Ok((remainder, identifier_name))
+    Ok((remainder, (identifier_name, type_annotation)))
 }
 
-pub fn parse_identifier_value(input: &str) -> IResult<&str, IdentifierValues> {
+/// Tables and functions take the name of the identifier they are assigned to.
+pub fn name_value(value: &mut IdentifierValues, name: &str) {
+    match value {
+        IdentifierValues::Table(t) => t.name = name.to_string(),
+        IdentifierValues::Function(func) => func.name = name.to_string(),
+        _ => {}
+    }
+}
+
+/// Parses a single literal value: nil, strings, numbers, booleans, tables and
+/// functions. These are the leaves of an expression.
+pub fn parse_literal(input: &str) -> IResult<&str, IdentifierValues> {
     alt((
-        map(tag("nil"), |_| IdentifierValues::Nil),
-        map(parse_string, |s| IdentifierValues::String(s)),
-        map(parse_number, |n| IdentifierValues::Number(n)),
-        map(parse_boolean, |b| IdentifierValues::Bool(b)),
-        map(parse_table, |t| IdentifierValues::Table(t)),
-        map(parse_function, |f| IdentifierValues::Function(f)),
-        map(parse_return, |ret_str| {
-            IdentifierValues::Return(ret_str.to_string())
+        map(keyword("nil"), |_| IdentifierValues::Nil),
+        map(parse_string, IdentifierValues::String),
+        map(parse_number, IdentifierValues::Number),
+        map(parse_boolean, IdentifierValues::Bool),
+        map(parse_table, IdentifierValues::Table),
+        map(parse_anonymous_function, |function| {
+            IdentifierValues::Function(Box::new(function))
         }),
     ))(input)
 }
 
+/// Unwraps expressions that are a single literal, so `local x = 1` stores a
+/// plain number rather than an expression.
+pub fn expression_value(expression: Expression) -> IdentifierValues {
+    match expression {
+        Expression::Value(value) => value,
+        expression => IdentifierValues::Expression(Box::new(expression)),
+    }
+}
+
+pub fn parse_identifier_value(input: &str) -> IResult<&str, IdentifierValues> {
+    map(parse_expression, expression_value)(input)
+}
+
 pub fn parse_identifier(input: &str) -> IResult<&str, Identifier> {
     let (remainder, (_, name, value, _)) = tuple((
         opt(parse_local),
         opt(identifier_name),
         parse_identifier_value,
-        opt(multispace0),
+        opt(whitespace0),
     ))(input)?;
 
-    match value {
-        IdentifierValues::Table(mut t) => {
-            t.name = name.unwrap().to_string();
-            Ok((
-                remainder,
-                Identifier {
-                    name: name.unwrap().to_string(),
-                    value: IdentifierValues::Table(t),
-                },
-            ))
-        }
-        IdentifierValues::Function(func) => Ok((
-            remainder,
-            Identifier {
-                name: func.name.clone(),
-                value: IdentifierValues::Function(func),
-            },
-        )),
-        IdentifierValues::Return(str) => Ok((
-            remainder,
-            Identifier {
-                name: String::from("Return"),
-                value: IdentifierValues::Return(str),
-            },
-        )),
-        _ => Ok((
-            remainder,
-            Identifier {
-                name: name.unwrap().to_string(),
-                value,
-            },
-        )),
-    }
+    let (name, type_annotation) = match name {
+        Some((name, type_annotation)) => (name.to_string(), type_annotation),
+        // a value on its own is not an identifier
+        None => return Err(nom::Err::Error(Error::new(input, ErrorKind::Verify))),
+    };
+
+    let mut value = value;
+    name_value(&mut value, &name);
+
+    Ok((
+        remainder,
+        Identifier {
+            name,
+            value,
+            type_annotation,
+        },
+    ))
 }
@@ -4,9 +4,11 @@ mod ast;
 mod block_parser;
 mod comment_parser;
 mod datatype_parsers;
+mod expression_parser;
 mod function_parser;
 mod identifier_parser;
 mod line_parser;
+mod statement_parser;
 mod table_parser;
 mod types;
 
@@ -14,77 +16,97 @@ mod types;
 mod tests {
     use std::{collections::HashMap, fs};
 
+    use nom::error::ErrorKind;
+
     use crate::{
         ast::make_ast,
         block_parser::{parse_block, Block, BlockType},
         comment_parser,
-        datatype_parsers::number_parser::parse_number,
-        datatype_parsers::string_parser::parse_string,
+        datatype_parsers::number_parser::{parse_number, NumberLiteral},
+        datatype_parsers::string_parser::{
+            parse_string, InterpolatedSegment, StringDelimiter, StringLiteral,
+        },
+        expression_parser::{parse_expression, BinaryOperator, Call, Expression, UnaryOperator},
         function_parser,
-        function_parser::{parse_function, Function, FunctionArguments},
+        function_parser::{parse_function, parse_local_function, Function, FunctionArguments},
         identifier_parser::{parse_identifier, Identifier, IdentifierValues},
         line_parser,
-        table_parser::{parse_table, Table, TableMember, TableMemberType},
-        types::Types,
+        statement_parser::{
+            parse_statement, Assignment, CompoundAssignment, Local, LocalVariable, Statement,
+        },
+        table_parser::{parse_table, Table, TableKey, TableMember, TableMemberType},
+        types::{
+            parse_type, GenericParameter, TypeAlias, TypeIndexer, TypePack, TypeProperty, Types,
+        },
     };
 
+    fn number(text: &str) -> IdentifierValues {
+        IdentifierValues::Number(parse_number(text).unwrap().1)
+    }
+
     #[test]
     fn test_blocks() {
         let contents = fs::read_to_string("tests/blocks.lua").unwrap();
+        let one = |value| Identifier {
+            name: "one".to_string(),
+            value,
+            type_annotation: None,
+        };
+        let local_one = |value| {
+            Statement::Local(Local {
+                variables: vec![LocalVariable {
+                    name: "one".to_string(),
+                    type_annotation: None,
+                }],
+                values: vec![Expression::Value(value)],
+            })
+        };
         let (remainder, do_block) = parse_block(&contents).unwrap();
         assert_eq!(
             do_block,
             Block {
                 block_type: BlockType::Do,
-                identifiers: HashMap::from([(
-                    "one".to_string(),
-                    Identifier {
-                        name: "one".to_string(),
-                        value: IdentifierValues::Number(1.)
-                    }
-                )])
+                identifiers: HashMap::from([("one".to_string(), one(number("1")))]),
+                statements: vec![local_one(number("1"))]
             }
         );
         let (r2, while_block) = parse_block(remainder).unwrap();
         assert_eq!(
             while_block,
             Block {
-                block_type: BlockType::While,
-                identifiers: HashMap::from([(
-                    "one".to_string(),
-                    Identifier {
-                        name: "one".to_string(),
-                        value: IdentifierValues::Number(1.)
-                    }
-                )])
+                block_type: BlockType::While(Box::new(Expression::Value(IdentifierValues::Bool(
+                    true
+                )))),
+                identifiers: HashMap::from([("one".to_string(), one(number("1")))]),
+                statements: vec![local_one(number("1"))]
             }
         );
         let (r3, repeat_block) = parse_block(r2).unwrap();
         assert_eq!(
             repeat_block,
             Block {
-                block_type: BlockType::Repeat,
-                identifiers: HashMap::from([(
-                    "one".to_string(),
-                    Identifier {
-                        name: "one".to_string(),
-                        value: IdentifierValues::Number(1.)
-                    }
-                )])
+                block_type: BlockType::Repeat(Box::new(Expression::Value(IdentifierValues::Bool(
+                    false
+                )))),
+                identifiers: HashMap::from([("one".to_string(), one(number("1")))]),
+                statements: vec![local_one(number("1"))]
             }
         );
         let (_, if_block) = parse_block(r3).unwrap();
         assert_eq!(
             if_block,
             Block {
-                block_type: BlockType::If,
-                identifiers: HashMap::from([(
-                    "one".to_string(),
-                    Identifier {
-                        name: "one".to_string(),
-                        value: IdentifierValues::Nil
-                    }
-                )])
+                block_type: BlockType::If {
+                    condition: Box::new(Expression::Binary {
+                        left: Box::new(Expression::Name("one".to_string())),
+                        operator: BinaryOperator::Equal,
+                        right: Box::new(Expression::Value(IdentifierValues::Bool(true)))
+                    }),
+                    else_ifs: vec![],
+                    else_block: None
+                },
+                identifiers: HashMap::from([("one".to_string(), one(IdentifierValues::Nil))]),
+                statements: vec![local_one(IdentifierValues::Nil)]
             }
         )
     }
@@ -101,11 +123,52 @@ mod tests {
     fn test_parse_function() {
         let contents = fs::read_to_string("tests/function_test.lua").unwrap();
         let (_remainder, function) = parse_function(&contents).unwrap();
+        let one = Identifier {
+            name: "one".to_string(),
+            value: number("1"),
+            type_annotation: None,
+        };
+        let two = Identifier {
+            name: "two".to_string(),
+            value: IdentifierValues::String(StringLiteral {
+                value: b"2".to_vec(),
+                delimiter: StringDelimiter::DoubleQuote,
+            }),
+            type_annotation: None,
+        };
+        let three = Identifier {
+            name: "three".to_string(),
+            value: IdentifierValues::Bool(true),
+            type_annotation: None,
+        };
+        let four = Identifier {
+            name: "four".to_string(),
+            value: IdentifierValues::Table(Table {
+                name: "four".to_string(),
+                members: vec![TableMember {
+                    key: TableKey::Name("one".to_string()),
+                    is_a: TableMemberType::RawType(number("1.0")),
+                }],
+            }),
+            type_annotation: None,
+        };
+        let local = |identifier: Identifier| {
+            Statement::Local(Local {
+                variables: vec![LocalVariable {
+                    name: identifier.name,
+                    type_annotation: None,
+                }],
+                values: vec![Expression::Value(identifier.value)],
+            })
+        };
         assert_eq!(
             function,
             Function {
                 name: "test".to_string(),
-                return_type: Types::Any,
+                path: vec![],
+                is_method: false,
+                generics: vec![],
+                returns: None,
                 arguments: vec![
                     FunctionArguments {
                         name: "a".to_string(),
@@ -116,76 +179,51 @@ mod tests {
                         function_type: Types::Any
                     }
                 ],
+                varargs: None,
                 identifiers: HashMap::<String, Identifier>::from([
-                    (
-                        "two".to_string(),
-                        Identifier {
-                            name: "two".to_string(),
-                            value: IdentifierValues::String("2".to_string())
-                        }
-                    ),
-                    (
-                        "three".to_string(),
-                        Identifier {
-                            name: "three".to_string(),
-                            value: IdentifierValues::Bool(true)
-                        }
-                    ),
-                    (
-                        "one".to_string(),
-                        Identifier {
-                            name: "one".to_string(),
-                            value: IdentifierValues::Number(1.0)
-                        }
-                    ),
-                    (
-                        "four".to_string(),
-                        Identifier {
-                            name: "four".to_string(),
-                            value: IdentifierValues::Table(Table {
-                                name: "four".to_string(),
-                                members: vec![TableMember {
-                                    name: "one".to_string(),
-                                    is_a: TableMemberType::RawType(IdentifierValues::Number(1.0))
-                                }]
-                            })
-                        }
-                    )
-                ])
+                    ("one".to_string(), one.clone()),
+                    ("two".to_string(), two.clone()),
+                    ("three".to_string(), three.clone()),
+                    ("four".to_string(), four.clone()),
+                ]),
+                statements: vec![local(one), local(two), local(three), local(four)]
             }
         );
     }
 
     #[test]
     fn test_parse_table() {
-        let input = "{a=1,\nb = 3\nc=\"tom\",d=true\ne={a=2.}}";
+        let input = "{a=1,\nb = 3;\nc=\"tom\",d=true,\ne={a=2.}}";
         let (_, table) = parse_table(input).unwrap();
         assert_eq!(
             table.members,
             vec![
                 TableMember {
-                    name: "a".to_string(),
-                    is_a: TableMemberType::RawType(IdentifierValues::Number(1.0))
+                    key: TableKey::Name("a".to_string()),
+                    is_a: TableMemberType::RawType(number("1"))
                 },
                 TableMember {
-                    name: "b".to_string(),
-                    is_a: TableMemberType::RawType(IdentifierValues::Number(3.0))
+                    key: TableKey::Name("b".to_string()),
+                    is_a: TableMemberType::RawType(number("3"))
                 },
                 TableMember {
-                    name: "c".to_string(),
-                    is_a: TableMemberType::RawType(IdentifierValues::String("tom".to_string()))
+                    key: TableKey::Name("c".to_string()),
+                    is_a: TableMemberType::RawType(IdentifierValues::String(StringLiteral {
+                        value: b"tom".to_vec(),
+                        delimiter: StringDelimiter::DoubleQuote
+                    }))
                 },
                 TableMember {
-                    name: "d".to_string(),
+                    key: TableKey::Name("d".to_string()),
                     is_a: TableMemberType::RawType(IdentifierValues::Bool(true))
                 },
                 TableMember {
-                    name: "e".to_string(),
+                    key: TableKey::Name("e".to_string()),
                     is_a: TableMemberType::NestedTable(Table {
                         name: "".to_string(),
                         members: vec![TableMember {
-                            name: "a".to_string(),
-                            is_a: TableMemberType::RawType(IdentifierValues::Number(2.0))
+                            key: TableKey::Name("a".to_string()),
+                            is_a: TableMemberType::RawType(number("2."))
                         },]
                     })
                 }
@@ -193,6 +231,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_table_constructor_fields() {
+        let input =
+            "{1, \"two\"; [\"a b\"] = 3, [key] = 4, name = function(x) return x end, [[five]]; }";
+        let (remainder, table) = parse_table(input).unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(table.members.len(), 6);
+        assert_eq!(
+            table.members[0],
+            TableMember {
+                key: TableKey::Index(1),
+                is_a: TableMemberType::RawType(number("1"))
+            }
+        );
+        assert_eq!(table.members[1].key, TableKey::Index(2));
+        assert_eq!(
+            table.members[2].key,
+            TableKey::Expression(Expression::Value(IdentifierValues::String(StringLiteral {
+                value: b"a b".to_vec(),
+                delimiter: StringDelimiter::DoubleQuote
+            })))
+        );
+        assert_eq!(
+            table.members[3].key,
+            TableKey::Expression(Expression::Name("key".to_string()))
+        );
+        assert_eq!(table.members[4].name(), Some("name"));
+        assert!(matches!(
+            &table.members[4].is_a,
+            TableMemberType::Function(function) if function.arguments.len() == 1
+        ));
+        assert_eq!(
+            table.members[5],
+            TableMember {
+                key: TableKey::Index(3),
+                is_a: TableMemberType::RawType(IdentifierValues::String(StringLiteral {
+                    value: b"five".to_vec(),
+                    delimiter: StringDelimiter::LongBracket(0)
+                }))
+            }
+        );
+
+        let (_, empty) = parse_table("{ }").unwrap();
+        assert!(empty.members.is_empty());
+
+        for invalid in ["{1 2 3}", "{a = 1 b = 2}", "{1,,}", "{,}"] {
+            assert!(parse_table(invalid).is_err(), "{invalid} should fail");
+        }
+    }
+
     #[test]
     fn test_parse_function_definition_types() {
         let line = "function tester_function(one: boolean, two: number)";
@@ -233,6 +321,395 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_type_grammar() {
+        let reference = |name: &str| Types::Reference {
+            name: name.to_string(),
+            generics: vec![],
+        };
+
+        let (_, parsed) = parse_type("number?").unwrap();
+        assert_eq!(parsed, Types::Optional(Box::new(Types::Number)));
+
+        let (_, parsed) = parse_type("string | number | nil").unwrap();
+        assert_eq!(
+            parsed,
+            Types::Union(vec![Types::String, Types::Number, Types::Nil])
+        );
+
+        let (_, parsed) = parse_type("A & (B | C)").unwrap();
+        assert_eq!(
+            parsed,
+            Types::Intersection(vec![
+                reference("A"),
+                Types::Union(vec![reference("B"), reference("C")])
+            ])
+        );
+
+        let (_, parsed) = parse_type("{ [string]: number }").unwrap();
+        assert_eq!(
+            parsed,
+            Types::TableType {
+                properties: vec![],
+                indexer: Some(Box::new(TypeIndexer {
+                    key: Types::String,
+                    value: Types::Number
+                }))
+            }
+        );
+
+        let (_, parsed) = parse_type("{ x: number, y: number; }").unwrap();
+        assert_eq!(
+            parsed,
+            Types::TableType {
+                properties: vec![
+                    TypeProperty {
+                        name: "x".to_string(),
+                        property_type: Types::Number
+                    },
+                    TypeProperty {
+                        name: "y".to_string(),
+                        property_type: Types::Number
+                    }
+                ],
+                indexer: None
+            }
+        );
+
+        let (_, parsed) = parse_type("(number, name: string) -> boolean").unwrap();
+        assert_eq!(
+            parsed,
+            Types::FunctionType {
+                generics: vec![],
+                arguments: vec![Types::Number, Types::String],
+                returns: TypePack::from(vec![Types::Boolean])
+            }
+        );
+
+        let (_, parsed) = parse_type("() -> ()").unwrap();
+        assert_eq!(
+            parsed,
+            Types::FunctionType {
+                generics: vec![],
+                arguments: vec![],
+                returns: TypePack::from(vec![])
+            }
+        );
+
+        let (_, parsed) = parse_type("Array<T>").unwrap();
+        assert_eq!(
+            parsed,
+            Types::Reference {
+                name: "Array".to_string(),
+                generics: vec![reference("T")]
+            }
+        );
+
+        let (_, parsed) = parse_type("{Module.Type}").unwrap();
+        assert_eq!(
+            parsed,
+            Types::TableType {
+                properties: vec![],
+                indexer: Some(Box::new(TypeIndexer {
+                    key: Types::Number,
+                    value: reference("Module.Type")
+                }))
+            }
+        );
+    }
+
+    #[test]
+    fn test_singleton_and_typeof_types() {
+        let (_, parsed) = parse_type("\"a\" | 'b'").unwrap();
+        assert_eq!(
+            parsed,
+            Types::Union(vec![
+                Types::StringSingleton(b"a".to_vec()),
+                Types::StringSingleton(b"b".to_vec())
+            ])
+        );
+
+        let (_, parsed) = parse_type("true").unwrap();
+        assert_eq!(parsed, Types::BooleanSingleton(true));
+
+        let (_, parsed) = parse_type("false?").unwrap();
+        assert_eq!(
+            parsed,
+            Types::Optional(Box::new(Types::BooleanSingleton(false)))
+        );
+
+        let (_, parsed) = parse_type("typeof(x)").unwrap();
+        assert_eq!(
+            parsed,
+            Types::Typeof(Box::new(Expression::Name("x".to_string())))
+        );
+
+        let (remainder, ast) =
+            make_ast("type Mode = \"a\" | \"b\"\ntype T = typeof(setmetatable({}, Class))\n")
+                .unwrap();
+        assert_eq!(remainder, "");
+        let Statement::TypeAlias(alias) = &ast.statements[1] else {
+            panic!("expected a type alias");
+        };
+        assert!(matches!(
+            &alias.alias_type,
+            Types::Typeof(expression) if matches!(**expression, Expression::Call(_))
+        ));
+    }
+
+    #[test]
+    fn test_string_keys_in_table_types() {
+        let property = |name: &str, property_type| TypeProperty {
+            name: name.to_string(),
+            property_type,
+        };
+
+        let (remainder, parsed) = parse_type("{ [\"a b\"]: number, [\"c\"]: string }").unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(
+            parsed,
+            Types::TableType {
+                properties: vec![property("a b", Types::Number), property("c", Types::String)],
+                indexer: None
+            }
+        );
+
+        let (remainder, parsed) = parse_type("{ [\"a\"]: number, [string]: any }").unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(
+            parsed,
+            Types::TableType {
+                properties: vec![property("a", Types::Number)],
+                indexer: Some(Box::new(TypeIndexer {
+                    key: Types::String,
+                    value: Types::Any
+                }))
+            }
+        );
+    }
+
+    #[test]
+    fn test_annotations_use_type_grammar() {
+        let line = "function f(list: {number}?, callback: (string) -> ()): string | nil";
+        let (_, function) = function_parser::parse_function_definition(line).unwrap();
+        assert_eq!(
+            function.arguments[0].function_type,
+            Types::Optional(Box::new(Types::TableType {
+                properties: vec![],
+                indexer: Some(Box::new(TypeIndexer {
+                    key: Types::Number,
+                    value: Types::Number
+                }))
+            }))
+        );
+        assert!(matches!(
+            function.arguments[1].function_type,
+            Types::FunctionType { .. }
+        ));
+        assert_eq!(
+            function.returns,
+            Some(TypePack::from(vec![Types::Union(vec![
+                Types::String,
+                Types::Nil
+            ])]))
+        );
+    }
+
+    #[test]
+    fn test_type_aliases() {
+        let lines = "type Id = number\nexport type Config = { name: string, retries: number? }\nexport type Map<K, V = string> = { [K]: V }\nlocal kind = type(x)\ntype = 1";
+        let (remainder, ast) = make_ast(lines).unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(ast.statements.len(), 5);
+        assert_eq!(
+            ast.statements[0],
+            Statement::TypeAlias(TypeAlias {
+                name: "Id".to_string(),
+                generics: vec![],
+                exported: false,
+                alias_type: Types::Number
+            })
+        );
+        assert!(matches!(ast.statements[3], Statement::Local(_)));
+        assert!(matches!(ast.statements[4], Statement::Assignment(_)));
+
+        let exported = ast.exported_types();
+        assert_eq!(
+            exported
+                .iter()
+                .map(|alias| alias.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Config", "Map"]
+        );
+        assert_eq!(
+            exported[1].generics,
+            vec![
+                GenericParameter {
+                    name: "K".to_string(),
+                    is_pack: false,
+                    default: None
+                },
+                GenericParameter {
+                    name: "V".to_string(),
+                    is_pack: false,
+                    default: Some(Types::String)
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generic_functions() {
+        let generic = |name: &str, is_pack| GenericParameter {
+            name: name.to_string(),
+            is_pack,
+            default: None,
+        };
+        let reference = |name: &str| Types::Reference {
+            name: name.to_string(),
+            generics: vec![],
+        };
+        let array = |value| Types::TableType {
+            properties: vec![],
+            indexer: Some(Box::new(TypeIndexer {
+                key: Types::Number,
+                value,
+            })),
+        };
+
+        let lines = "function map<T, U>(list: {T}, f: (T) -> U): {U}\nend";
+        let (remainder, function) = parse_function(lines).unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(function.name, "map");
+        assert_eq!(
+            function.generics,
+            vec![generic("T", false), generic("U", false)]
+        );
+        assert_eq!(function.arguments[0].function_type, array(reference("T")));
+        assert_eq!(
+            function.arguments[1].function_type,
+            Types::FunctionType {
+                generics: vec![],
+                arguments: vec![reference("T")],
+                returns: TypePack::from(vec![reference("U")])
+            }
+        );
+        assert_eq!(
+            function.returns,
+            Some(TypePack::from(vec![array(reference("U"))]))
+        );
+
+        let lines = "local pack = function<A...>(...: A...) return ... end";
+        let (_, ast) = make_ast(lines).unwrap();
+        let IdentifierValues::Function(anonymous) = &ast.identifiers["pack"].value else {
+            panic!("expected a function");
+        };
+        assert_eq!(anonymous.generics, vec![generic("A", true)]);
+        assert_eq!(anonymous.varargs, Some(Types::GenericPack("A".to_string())));
+
+        let (_, parsed) = parse_type("<T>(T) -> T").unwrap();
+        assert_eq!(
+            parsed,
+            Types::FunctionType {
+                generics: vec![generic("T", false)],
+                arguments: vec![reference("T")],
+                returns: TypePack::from(vec![reference("T")])
+            }
+        );
+        assert!(parse_type("<T>(T)").is_err());
+    }
+
+    #[test]
+    fn test_nested_type_packs() {
+        let depth = 30;
+        let mut pack = "(A, B)".to_string();
+        for _ in 0..depth {
+            pack = format!("(() -> {pack}, B)");
+        }
+        let function_type = format!("() -> {pack}");
+        let (remainder, mut parsed) = parse_type(&function_type).unwrap();
+        assert_eq!(remainder, "");
+
+        for _ in 0..=depth {
+            let Types::FunctionType { returns, .. } = parsed else {
+                panic!("expected a function type");
+            };
+            assert_eq!(returns.types.len(), 2);
+            parsed = returns.types[0].clone();
+        }
+        assert_eq!(
+            parsed,
+            Types::Reference {
+                name: "A".to_string(),
+                generics: vec![]
+            }
+        );
+
+        let (_, parsed) = parse_type("() -> (A)? | B").unwrap();
+        assert!(matches!(
+            parsed,
+            Types::FunctionType { returns, .. }
+                if matches!(returns.types[..], [Types::Union(ref types)] if types.len() == 2)
+        ));
+    }
+
+    #[test]
+    fn test_return_type_packs() {
+        let returns = |line: &str| {
+            let (_, function) = function_parser::parse_function_definition(line).unwrap();
+            function.returns.unwrap()
+        };
+
+        assert_eq!(
+            returns("function f(): (number, string)"),
+            TypePack {
+                types: vec![Types::Number, Types::String],
+                tail: None
+            }
+        );
+        assert_eq!(
+            returns("function f(): ()"),
+            TypePack {
+                types: vec![],
+                tail: None
+            }
+        );
+        assert_eq!(
+            returns("function f(): ...number"),
+            TypePack {
+                types: vec![],
+                tail: Some(Box::new(Types::Variadic(Box::new(Types::Number))))
+            }
+        );
+        assert_eq!(
+            returns("function f<T...>(): (boolean, T...)"),
+            TypePack {
+                types: vec![Types::Boolean],
+                tail: Some(Box::new(Types::GenericPack("T".to_string())))
+            }
+        );
+        assert!(matches!(
+            returns("function f(): (number) -> string").types[..],
+            [Types::FunctionType { .. }]
+        ));
+
+        let (_, parsed) = parse_type("(string, ...any) -> (boolean, ...string)").unwrap();
+        assert_eq!(
+            parsed,
+            Types::FunctionType {
+                generics: vec![],
+                arguments: vec![Types::String, Types::Variadic(Box::new(Types::Any))],
+                returns: TypePack {
+                    types: vec![Types::Boolean],
+                    tail: Some(Box::new(Types::Variadic(Box::new(Types::String))))
+                }
+            }
+        );
+
+        let (_, function) = function_parser::parse_function_definition("function f()").unwrap();
+        assert_eq!(function.returns, None);
+    }
+
     #[test]
     fn test_comment_line() {
         let line = "-- this is a comment\n";
@@ -250,6 +727,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_comment_levels_and_eof() {
+        let block = "--[==[ closes with ]] or ]=] only at ]==] rest";
+        let (remainder, comment) = comment_parser::parse_comment_block(block).unwrap();
+        assert_eq!(comment, " closes with ]] or ]=] only at ");
+        assert_eq!(remainder, " rest");
+
+        let (remainder, comment) = comment_parser::parse_comment_line("-- last line").unwrap();
+        assert_eq!(comment, " last line");
+        assert_eq!(remainder, "");
+
+        // not a long bracket, so the comment ends with the line
+        let (remainder, comment) = comment_parser::parse_comment("--[= x\ny").unwrap();
+        assert_eq!(comment, "[= x");
+        assert_eq!(remainder, "y");
+    }
+
+    #[test]
+    fn test_comments_as_whitespace() {
+        let source = "-- header\nlocal t = { -- first\n a = 1, --[[ inline ]] b = 2 --[=[ last ]=]\n}\nprint(--[[ arg ]] t.a, -- next\n t.b)\n--[==[\nend of file\n]==]\nreturn t -- done";
+        let (remainder, ast) = make_ast(source).unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(ast.statements.len(), 3);
+        let Some(Identifier {
+            value: IdentifierValues::Table(table),
+            ..
+        }) = ast.identifiers.get("t")
+        else {
+            panic!("expected t to be a table");
+        };
+        assert_eq!(table.members.len(), 2);
+        let Statement::Call(call) = &ast.statements[1] else {
+            panic!("expected a call");
+        };
+        assert_eq!(call.arguments.len(), 2);
+    }
+
     #[test]
     fn test_multiline_identifiers() {
         let lines = "local test = 1\nlocal other_test = \"2\"\n";
@@ -258,11 +772,14 @@ mod tests {
         let (_, line_2) = line_parser::parse_line(remaining_lines).unwrap();
         let (_remainder, identifier_2) = parse_identifier(line_2).unwrap();
         assert_eq!(identifier_1.name, "test");
-        assert_eq!(identifier_1.value, IdentifierValues::Number(1 as f32));
+        assert_eq!(identifier_1.value, number("1"));
         assert_eq!(identifier_2.name, "other_test");
         assert_eq!(
             identifier_2.value,
-            IdentifierValues::String(String::from("2"))
+            IdentifierValues::String(StringLiteral {
+                value: b"2".to_vec(),
+                delimiter: StringDelimiter::DoubleQuote
+            })
         );
     }
 
@@ -280,7 +797,7 @@ mod tests {
         let identifier_string = "local test = 1";
         let (_remainder, identifier) = parse_identifier(identifier_string).unwrap();
         assert_eq!(identifier.name, "test");
-        assert_eq!(identifier.value, IdentifierValues::Number(1 as f32));
+        assert_eq!(identifier.value, number("1"));
     }
 
     #[test]
@@ -290,7 +807,10 @@ mod tests {
         assert_eq!(identifier.name, "test");
         assert_eq!(
             identifier.value,
-            IdentifierValues::String(String::from("tom"))
+            IdentifierValues::String(StringLiteral {
+                value: b"tom".to_vec(),
+                delimiter: StringDelimiter::DoubleQuote
+            })
         );
     }
 
@@ -298,41 +818,1084 @@ mod tests {
     fn test_string() {
         let str = "\"string thing   thing\"";
         let (_, parsed_str) = parse_string::<()>(str).unwrap();
-        assert_eq!(parsed_str, "string thing   thing");
+        assert_eq!(parsed_str.value, b"string thing   thing");
+        assert_eq!(parsed_str.delimiter, StringDelimiter::DoubleQuote);
     }
 
     #[test]
     fn test_integer() {
         let int = "6";
         let (_, int_value) = parse_number(int).unwrap();
-        assert_eq!(int_value, 6 as f32);
+        assert_eq!(int_value.value, 6.);
     }
 
     #[test]
     fn test_float_with_dot() {
         let int = "6.";
         let (_, int_value) = parse_number(int).unwrap();
-        assert_eq!(int_value, 6.);
+        assert_eq!(int_value.value, 6.);
     }
 
     #[test]
     fn test_float_with_decimal() {
         let int = "6.160";
         let (_, int_value) = parse_number(int).unwrap();
-        assert_eq!(int_value, 6.160);
+        assert_eq!(int_value.value, 6.160);
     }
 
     #[test]
     fn test_decimal() {
         let int = ".160";
         let (_, int_value) = parse_number(int).unwrap();
-        assert_eq!(int_value, 0.160);
+        assert_eq!(int_value.value, 0.160);
     }
 
     #[test]
     fn test_decimal_leading_zero() {
         let int = "0.160";
         let (_, int_value) = parse_number(int).unwrap();
-        assert_eq!(int_value, 0.160);
+        assert_eq!(int_value.value, 0.160);
+    }
+
+    #[test]
+    fn test_number_precision() {
+        let (_, number) = parse_number("16777217").unwrap();
+        assert_eq!(
+            number,
+            NumberLiteral {
+                text: "16777217".to_string(),
+                value: 16777217.,
+                integer: Some(16777217)
+            }
+        );
+
+        let (_, number) = parse_number("9223372036854775808").unwrap();
+        assert_eq!(number.integer, None);
+        assert_eq!(number.value, 9223372036854775808.);
+
+        let (_, number) = parse_number("1e3").unwrap();
+        assert_eq!(number.value, 1000.);
+        assert_eq!(number.integer, None);
+    }
+
+    #[test]
+    fn test_number_radix_and_separators() {
+        let (_, number) = parse_number("0xFF").unwrap();
+        assert_eq!((number.value, number.integer), (255., Some(255)));
+
+        let (_, number) = parse_number("0b1010").unwrap();
+        assert_eq!((number.value, number.integer), (10., Some(10)));
+
+        let (_, number) = parse_number("0x_7FFF_FFFF").unwrap();
+        assert_eq!(number.integer, Some(0x7FFF_FFFF));
+        assert_eq!(number.text, "0x_7FFF_FFFF");
+
+        let (_, number) = parse_number("1_000_000.5").unwrap();
+        assert_eq!((number.value, number.integer), (1_000_000.5, None));
+
+        let (_, number) = parse_number("0xFFFFFFFFFFFFFFFF").unwrap();
+        assert_eq!(number.integer, Some(-1));
+
+        assert!(parse_number("0xFG").is_err());
+        assert!(parse_number("0b102").is_err());
+    }
+
+    #[test]
+    fn test_identifier_expression() {
+        let identifier_string = "local x = a + b * 2";
+        let (_remainder, identifier) = parse_identifier(identifier_string).unwrap();
+        assert_eq!(identifier.name, "x");
+        assert_eq!(
+            identifier.value,
+            IdentifierValues::Expression(Box::new(Expression::Binary {
+                left: Box::new(Expression::Name("a".to_string())),
+                operator: BinaryOperator::Add,
+                right: Box::new(Expression::Binary {
+                    left: Box::new(Expression::Name("b".to_string())),
+                    operator: BinaryOperator::Multiply,
+                    right: Box::new(Expression::Value(number("2")))
+                })
+            }))
+        );
+    }
+
+    #[test]
+    fn test_booleans_are_case_sensitive() {
+        assert_eq!(
+            parse_expression("True"),
+            Ok(("", Expression::Name("True".to_string())))
+        );
+        assert_eq!(
+            parse_expression("FALSE"),
+            Ok(("", Expression::Name("FALSE".to_string())))
+        );
+        assert_eq!(
+            parse_expression("true"),
+            Ok(("", Expression::Value(IdentifierValues::Bool(true))))
+        );
+    }
+
+    #[test]
+    fn test_expression_right_associative() {
+        let (_, expression) = parse_expression("a .. b .. c").unwrap();
+        assert_eq!(
+            expression,
+            Expression::Binary {
+                left: Box::new(Expression::Name("a".to_string())),
+                operator: BinaryOperator::Concat,
+                right: Box::new(Expression::Binary {
+                    left: Box::new(Expression::Name("b".to_string())),
+                    operator: BinaryOperator::Concat,
+                    right: Box::new(Expression::Name("c".to_string()))
+                })
+            }
+        );
+    }
+
+    #[test]
+    fn test_expression_unary_and_power() {
+        let (_, expression) = parse_expression("-x ^ 2 // 3").unwrap();
+        assert_eq!(
+            expression,
+            Expression::Binary {
+                left: Box::new(Expression::Unary {
+                    operator: UnaryOperator::Negate,
+                    operand: Box::new(Expression::Binary {
+                        left: Box::new(Expression::Name("x".to_string())),
+                        operator: BinaryOperator::Power,
+                        right: Box::new(Expression::Value(number("2")))
+                    })
+                }),
+                operator: BinaryOperator::FloorDivide,
+                right: Box::new(Expression::Value(number("3")))
+            }
+        );
+    }
+
+    #[test]
+    fn test_expression_logical() {
+        let (remainder, expression) =
+            parse_expression("not a == b or #c > 1 and d -- done").unwrap();
+        assert_eq!(remainder, " -- done");
+        assert_eq!(
+            expression,
+            Expression::Binary {
+                left: Box::new(Expression::Binary {
+                    left: Box::new(Expression::Unary {
+                        operator: UnaryOperator::Not,
+                        operand: Box::new(Expression::Name("a".to_string()))
+                    }),
+                    operator: BinaryOperator::Equal,
+                    right: Box::new(Expression::Name("b".to_string()))
+                }),
+                operator: BinaryOperator::Or,
+                right: Box::new(Expression::Binary {
+                    left: Box::new(Expression::Binary {
+                        left: Box::new(Expression::Unary {
+                            operator: UnaryOperator::Length,
+                            operand: Box::new(Expression::Name("c".to_string()))
+                        }),
+                        operator: BinaryOperator::GreaterThan,
+                        right: Box::new(Expression::Value(number("1")))
+                    }),
+                    operator: BinaryOperator::And,
+                    right: Box::new(Expression::Name("d".to_string()))
+                })
+            }
+        );
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let (remainder, expression) =
+            parse_expression("if a then 1 elseif b then 2 else c .. \"d\"").unwrap();
+        assert_eq!(remainder, "");
+        let name = |name: &str| Expression::Name(name.to_string());
+        assert_eq!(
+            expression,
+            Expression::If {
+                condition: Box::new(name("a")),
+                then_value: Box::new(Expression::Value(number("1"))),
+                else_ifs: vec![(name("b"), Expression::Value(number("2")))],
+                else_value: Box::new(Expression::Binary {
+                    left: Box::new(name("c")),
+                    operator: BinaryOperator::Concat,
+                    right: Box::new(Expression::Value(IdentifierValues::String(StringLiteral {
+                        value: b"d".to_vec(),
+                        delimiter: StringDelimiter::DoubleQuote
+                    })))
+                })
+            }
+        );
+
+        let (remainder, ast) =
+            make_ast("local x = if ready then go() else wait()\nif x then end").unwrap();
+        assert_eq!(remainder, "");
+        assert!(matches!(&ast.statements[1], Statement::Block(_)));
+    }
+
+    #[test]
+    fn test_if_expression_requires_else() {
+        assert!(matches!(
+            parse_expression("if a then 1"),
+            Err(nom::Err::Failure(_))
+        ));
+        assert!(matches!(
+            make_ast("local x = if a then 1 elseif b then 2\n"),
+            Err(nom::Err::Failure(_))
+        ));
+    }
+
+    #[test]
+    fn test_type_assertion() {
+        let (remainder, expression) = parse_expression("(value :: any) :: Player").unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(
+            expression,
+            Expression::TypeAssertion {
+                expression: Box::new(Expression::Parentheses(Box::new(
+                    Expression::TypeAssertion {
+                        expression: Box::new(Expression::Name("value".to_string())),
+                        asserted_type: Types::Any
+                    }
+                ))),
+                asserted_type: Types::Reference {
+                    name: "Player".to_string(),
+                    generics: vec![]
+                }
+            }
+        );
+
+        // the assertion applies to `b` alone, and to the operand of `-`
+        let (_, expression) = parse_expression("a + -b :: number").unwrap();
+        assert_eq!(
+            expression,
+            Expression::Binary {
+                left: Box::new(Expression::Name("a".to_string())),
+                operator: BinaryOperator::Add,
+                right: Box::new(Expression::Unary {
+                    operator: UnaryOperator::Negate,
+                    operand: Box::new(Expression::TypeAssertion {
+                        expression: Box::new(Expression::Name("b".to_string())),
+                        asserted_type: Types::Number
+                    })
+                })
+            }
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let (_, statement) = parse_statement("t.count += 1").unwrap();
+        assert_eq!(
+            statement,
+            Statement::CompoundAssignment(CompoundAssignment {
+                target: Box::new(Expression::Field {
+                    table: Box::new(Expression::Name("t".to_string())),
+                    name: "count".to_string()
+                }),
+                operator: BinaryOperator::Add,
+                value: Box::new(Expression::Value(number("1")))
+            })
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment_operators() {
+        let lines = "x //= 2\nname ..= \"!\"\nx = 3";
+        let (_, ast) = make_ast(lines).unwrap();
+        assert_eq!(
+            ast.statements,
+            vec![
+                Statement::CompoundAssignment(CompoundAssignment {
+                    target: Box::new(Expression::Name("x".to_string())),
+                    operator: BinaryOperator::FloorDivide,
+                    value: Box::new(Expression::Value(number("2")))
+                }),
+                Statement::CompoundAssignment(CompoundAssignment {
+                    target: Box::new(Expression::Name("name".to_string())),
+                    operator: BinaryOperator::Concat,
+                    value: Box::new(Expression::Value(IdentifierValues::String(StringLiteral {
+                        value: b"!".to_vec(),
+                        delimiter: StringDelimiter::DoubleQuote
+                    })))
+                }),
+                Statement::Assignment(Assignment {
+                    targets: vec![Expression::Name("x".to_string())],
+                    values: vec![Expression::Value(number("3"))]
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn test_numeric_for() {
+        let block = "for i = 1, n, 2 do\n    local double = i * 2\nend";
+        let (_, for_block) = parse_block(block).unwrap();
+        assert_eq!(
+            for_block.block_type,
+            BlockType::NumericFor {
+                variable: LocalVariable {
+                    name: "i".to_string(),
+                    type_annotation: None
+                },
+                start: Box::new(Expression::Value(number("1"))),
+                limit: Box::new(Expression::Name("n".to_string())),
+                step: Some(Box::new(Expression::Value(number("2"))))
+            }
+        );
+        assert_eq!(for_block.identifiers["i"].value, number("1"));
+        assert!(for_block.identifiers.contains_key("double"));
+    }
+
+    #[test]
+    fn test_generic_for() {
+        let block = "for key, value in next, tab do\n    local copy = value\nend";
+        let (_, for_block) = parse_block(block).unwrap();
+        assert_eq!(
+            for_block.block_type,
+            BlockType::GenericFor {
+                variables: vec![
+                    LocalVariable {
+                        name: "key".to_string(),
+                        type_annotation: None
+                    },
+                    LocalVariable {
+                        name: "value".to_string(),
+                        type_annotation: None
+                    }
+                ],
+                expressions: vec![
+                    Expression::Name("next".to_string()),
+                    Expression::Name("tab".to_string())
+                ]
+            }
+        );
+        assert_eq!(
+            for_block.identifiers["key"].value,
+            IdentifierValues::Unknown
+        );
+        assert_eq!(
+            for_block.identifiers["value"].value,
+            IdentifierValues::Unknown
+        );
+        assert_eq!(for_block.statements.len(), 1);
+    }
+
+    #[test]
+    fn test_assignment_keeps_declared_type() {
+        let (remainder, ast) = make_ast("local n: number = 1\nn = 5\nm = 2\n").unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(
+            ast.identifiers["n"],
+            Identifier {
+                name: "n".to_string(),
+                value: number("5"),
+                type_annotation: Some(Types::Number)
+            }
+        );
+        assert_eq!(ast.identifiers["m"].type_annotation, None);
+    }
+
+    #[test]
+    fn test_annotated_declarations() {
+        let (_, identifier) = parse_identifier("local count: number = 0").unwrap();
+        assert_eq!(
+            identifier,
+            Identifier {
+                name: "count".to_string(),
+                value: number("0"),
+                type_annotation: Some(Types::Number)
+            }
+        );
+
+        let lines = "local map: {[string]: Player} = {}\nlocal id: string?, total = nil, 1";
+        let (remainder, ast) = make_ast(lines).unwrap();
+        assert_eq!(remainder, "");
+        let map = &ast.identifiers["map"];
+        assert!(matches!(map.value, IdentifierValues::Table(_)));
+        assert!(matches!(
+            &map.type_annotation,
+            Some(Types::TableType { indexer: Some(indexer), .. }) if indexer.key == Types::String
+        ));
+        assert_eq!(ast.identifiers["id"].value, IdentifierValues::Nil);
+        assert_eq!(
+            ast.identifiers["id"].type_annotation,
+            Some(Types::Optional(Box::new(Types::String)))
+        );
+        assert_eq!(ast.identifiers["total"].type_annotation, None);
+
+        let (_, for_block) = parse_block("for i: number = 1, 10 do end").unwrap();
+        assert_eq!(
+            for_block.identifiers["i"].type_annotation,
+            Some(Types::Number)
+        );
+
+        let (_, for_block) =
+            parse_block("for key: string, value: Player in pairs(players) do end").unwrap();
+        let BlockType::GenericFor { variables, .. } = &for_block.block_type else {
+            panic!("expected a generic for");
+        };
+        assert_eq!(variables[0].type_annotation, Some(Types::String));
+        assert_eq!(
+            for_block.identifiers["value"].type_annotation,
+            Some(Types::Reference {
+                name: "Player".to_string(),
+                generics: vec![]
+            })
+        );
+    }
+
+    #[test]
+    fn test_if_elseif_else() {
+        let block = "if a then\n    local one = 1\nelseif b then\n    local two = 2\nelseif c then\nelse\n    local three = 3\nend";
+        let (remainder, if_block) = parse_block(block).unwrap();
+        assert_eq!(remainder, "");
+        assert!(if_block.identifiers.contains_key("one"));
+        let BlockType::If {
+            condition,
+            else_ifs,
+            else_block,
+        } = if_block.block_type
+        else {
+            panic!("expected an if block");
+        };
+        assert_eq!(*condition, Expression::Name("a".to_string()));
+        assert_eq!(else_ifs.len(), 2);
+        assert_eq!(
+            else_ifs[0].block_type,
+            BlockType::ElseIf(Box::new(Expression::Name("b".to_string())))
+        );
+        assert!(else_ifs[0].identifiers.contains_key("two"));
+        assert!(else_ifs[1].statements.is_empty());
+        let else_block = else_block.unwrap();
+        assert_eq!(else_block.block_type, BlockType::Else);
+        assert!(else_block.identifiers.contains_key("three"));
+    }
+
+    #[test]
+    fn test_nested_blocks() {
+        let contents = fs::read_to_string("tests/nested.lua").unwrap();
+        let (remainder, function) = parse_function(&contents).unwrap();
+        assert_eq!(remainder.trim(), "");
+        assert_eq!(function.statements.len(), 3);
+        assert!(function.identifiers.contains_key("sender"));
+        assert!(function.identifiers.contains_key("after"));
+
+        let Statement::Block(if_block) = &function.statements[1] else {
+            panic!("expected an if block");
+        };
+        let Statement::Block(for_block) = &if_block.statements[0] else {
+            panic!("expected a for block");
+        };
+        assert!(for_block.identifiers.contains_key("ending"));
+        let Statement::Block(inner_if) = &for_block.statements[1] else {
+            panic!("expected a nested if block");
+        };
+        assert_eq!(inner_if.statements, vec![Statement::Break]);
+
+        let BlockType::If { else_block, .. } = &if_block.block_type else {
+            panic!("expected an if block");
+        };
+        let else_block = else_block.as_ref().unwrap();
+        let Statement::Block(while_block) = &else_block.statements[0] else {
+            panic!("expected a while block");
+        };
+        assert!(matches!(
+            while_block.statements[0],
+            Statement::CompoundAssignment(_)
+        ));
+    }
+
+    #[test]
+    fn test_chained_calls() {
+        let (_, expression) = parse_expression("a.b:c()(d)").unwrap();
+        assert_eq!(
+            expression,
+            Expression::Call(Call {
+                function: Box::new(Expression::Call(Call {
+                    function: Box::new(Expression::Field {
+                        table: Box::new(Expression::Name("a".to_string())),
+                        name: "b".to_string()
+                    }),
+                    method: Some("c".to_string()),
+                    arguments: vec![]
+                })),
+                method: None,
+                arguments: vec![Expression::Name("d".to_string())]
+            })
+        );
+    }
+
+    #[test]
+    fn test_call_statements() {
+        let lines = "print(\"hi\")\nobj:Method(1, x + 2)\nf{}\nf\"str\"\n";
+        let (remainder, ast) = make_ast(lines).unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(
+            ast.statements,
+            vec![
+                Statement::Call(Call {
+                    function: Box::new(Expression::Name("print".to_string())),
+                    method: None,
+                    arguments: vec![Expression::Value(IdentifierValues::String(StringLiteral {
+                        value: b"hi".to_vec(),
+                        delimiter: StringDelimiter::DoubleQuote
+                    }))]
+                }),
+                Statement::Call(Call {
+                    function: Box::new(Expression::Name("obj".to_string())),
+                    method: Some("Method".to_string()),
+                    arguments: vec![
+                        Expression::Value(number("1")),
+                        Expression::Binary {
+                            left: Box::new(Expression::Name("x".to_string())),
+                            operator: BinaryOperator::Add,
+                            right: Box::new(Expression::Value(number("2")))
+                        }
+                    ]
+                }),
+                Statement::Call(Call {
+                    function: Box::new(Expression::Name("f".to_string())),
+                    method: None,
+                    arguments: vec![Expression::Value(IdentifierValues::Table(Table {
+                        name: "".to_string(),
+                        members: vec![]
+                    }))]
+                }),
+                Statement::Call(Call {
+                    function: Box::new(Expression::Name("f".to_string())),
+                    method: None,
+                    arguments: vec![Expression::Value(IdentifierValues::String(StringLiteral {
+                        value: b"str".to_vec(),
+                        delimiter: StringDelimiter::DoubleQuote
+                    }))]
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_callbacks() {
+        let depth = 30;
+        let lines = format!(
+            "{}x = 1\n{}",
+            "x:Connect(function()\n".repeat(depth),
+            "end)\n".repeat(depth)
+        );
+        let (remainder, ast) = make_ast(&lines).unwrap();
+        assert_eq!(remainder, "");
+
+        let mut statements = ast.statements;
+        for _ in 0..depth {
+            let [Statement::Call(call)] = statements.as_slice() else {
+                panic!("expected a single call");
+            };
+            let [Expression::Value(IdentifierValues::Function(callback))] =
+                call.arguments.as_slice()
+            else {
+                panic!("expected a callback");
+            };
+            statements = callback.statements.clone();
+        }
+        assert!(matches!(statements.as_slice(), [Statement::Assignment(_)]));
+    }
+
+    #[test]
+    fn test_call_expression_value() {
+        let (_, identifier) = parse_identifier("local total = math.max(a, b) * 2").unwrap();
+        let IdentifierValues::Expression(expression) = identifier.value else {
+            panic!("expected an expression");
+        };
+        let Expression::Binary { left, .. } = *expression else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!(
+            *left,
+            Expression::Call(Call {
+                function: Box::new(Expression::Field {
+                    table: Box::new(Expression::Name("math".to_string())),
+                    name: "max".to_string()
+                }),
+                method: None,
+                arguments: vec![
+                    Expression::Name("a".to_string()),
+                    Expression::Name("b".to_string())
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_table_function_declarations() {
+        let line = "function a.b.c:d(one)";
+        let (_, function) = function_parser::parse_function_definition(line).unwrap();
+        assert_eq!(function.name, "d");
+        assert_eq!(function.path, vec!["a", "b", "c"]);
+        assert!(function.is_method);
+        assert_eq!(function.full_name(), "a.b.c:d");
+
+        let contents = fs::read_to_string("tests/methods.lua").unwrap();
+        let (remainder, ast) = make_ast(&contents).unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(ast.statements.len(), 6);
+        assert!(!ast.identifiers.contains_key("other.thing"));
+
+        let IdentifierValues::Table(tab) = &ast.identifiers["tab"].value else {
+            panic!("expected a table");
+        };
+        assert!(matches!(
+            &tab.members[0],
+            TableMember { key: TableKey::Name(name), is_a: TableMemberType::Function(_) } if name == "thing"
+        ));
+        assert!(matches!(
+            &tab.members[1],
+            TableMember { key: TableKey::Name(name), is_a: TableMemberType::Method(_) } if name == "method"
+        ));
+
+        let IdentifierValues::Table(nested) = &ast.identifiers["nested"].value else {
+            panic!("expected a table");
+        };
+        let TableMemberType::NestedTable(inner) = &nested.members[0].is_a else {
+            panic!("expected a nested table");
+        };
+        assert!(matches!(
+            &inner.members[0],
+            TableMember { key: TableKey::Name(name), is_a: TableMemberType::Method(_) } if name == "deep"
+        ));
+    }
+
+    #[test]
+    fn test_index_expressions() {
+        let (remainder, expression) = parse_expression("t[i + 1].name").unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(
+            expression,
+            Expression::Field {
+                table: Box::new(Expression::Index {
+                    table: Box::new(Expression::Name("t".to_string())),
+                    key: Box::new(Expression::Binary {
+                        left: Box::new(Expression::Name("i".to_string())),
+                        operator: BinaryOperator::Add,
+                        right: Box::new(Expression::Value(number("1")))
+                    })
+                }),
+                name: "name".to_string()
+            }
+        );
+
+        // a long bracket string is still a call argument
+        let (_, expression) = parse_expression("f[[text]]").unwrap();
+        assert!(matches!(expression, Expression::Call(_)));
+
+        let (_, statement) = parse_statement("t[i] = v").unwrap();
+        assert_eq!(
+            statement,
+            Statement::Assignment(Assignment {
+                targets: vec![Expression::Index {
+                    table: Box::new(Expression::Name("t".to_string())),
+                    key: Box::new(Expression::Name("i".to_string()))
+                }],
+                values: vec![Expression::Name("v".to_string())]
+            })
+        );
+    }
+
+    #[test]
+    fn test_field_writes() {
+        let lines = "local tab = { inner = {} }\ntab.t = function(a, b) end\ntab.inner.health = 100\ntab[\"key\"] = true\nother.x = 1";
+        let (remainder, ast) = make_ast(lines).unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(ast.statements.len(), 5);
+
+        let IdentifierValues::Table(table) = &ast.identifiers["tab"].value else {
+            panic!("expected a table");
+        };
+        assert_eq!(table.members.len(), 3);
+        assert!(matches!(
+            &table.members[1],
+            TableMember { key: TableKey::Name(name), is_a: TableMemberType::Function(function) }
+                if name == "t" && function.name == "t" && function.arguments.len() == 2
+        ));
+        assert!(matches!(
+            &table.members[0].is_a,
+            TableMemberType::NestedTable(inner) if inner.members == vec![TableMember {
+                key: TableKey::Name("health".to_string()),
+                is_a: TableMemberType::RawType(number("100"))
+            }]
+        ));
+        assert_eq!(
+            table.members[2],
+            TableMember {
+                key: TableKey::Expression(Expression::Value(IdentifierValues::String(
+                    StringLiteral {
+                        value: b"key".to_vec(),
+                        delimiter: StringDelimiter::DoubleQuote
+                    }
+                ))),
+                is_a: TableMemberType::RawType(IdentifierValues::Bool(true))
+            }
+        );
+        assert!(!ast.identifiers.contains_key("other"));
+    }
+
+    #[test]
+    fn test_literal_index_writes() {
+        let lines = "local t = {10, x = 1}\nt[1] = 20\nt[\"x\"] = 2\nt[\"y\"] = 3\n";
+        let (remainder, ast) = make_ast(lines).unwrap();
+        assert_eq!(remainder, "");
+        let IdentifierValues::Table(table) = &ast.identifiers["t"].value else {
+            panic!("expected a table");
+        };
+        assert_eq!(table.members.len(), 3);
+        assert_eq!(
+            table.members[0],
+            TableMember {
+                key: TableKey::Index(1),
+                is_a: TableMemberType::RawType(number("20"))
+            }
+        );
+        assert_eq!(
+            table.members[1],
+            TableMember {
+                key: TableKey::Name("x".to_string()),
+                is_a: TableMemberType::RawType(number("2"))
+            }
+        );
+    }
+
+    #[test]
+    fn test_anonymous_functions() {
+        let lines = "local otherFunction = function(a, b)\n    return a\nend\nlocal t = {\n    callback = function() end\n}\ncall(function(x) x += 1 end)\n";
+        let (remainder, ast) = make_ast(lines).unwrap();
+        assert_eq!(remainder, "");
+
+        let IdentifierValues::Function(function) = &ast.identifiers["otherFunction"].value else {
+            panic!("expected a function");
+        };
+        assert_eq!(function.name, "otherFunction");
+        assert_eq!(function.arguments.len(), 2);
+        assert_eq!(function.statements.len(), 1);
+
+        let IdentifierValues::Table(table) = &ast.identifiers["t"].value else {
+            panic!("expected a table");
+        };
+        assert!(matches!(
+            &table.members[0].is_a,
+            TableMemberType::Function(callback) if callback.arguments.is_empty()
+        ));
+
+        let Statement::Call(call) = &ast.statements[2] else {
+            panic!("expected a call");
+        };
+        assert!(matches!(
+            &call.arguments[0],
+            Expression::Value(IdentifierValues::Function(argument)) if argument.name.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_varargs() {
+        let lines = "local function f(a, ...: number)\n    local args = {...}\n    return select(\"#\", ...)\nend\nlocal g = function(...) return ... end\nprint(...)";
+        let (remainder, ast) = make_ast(lines).unwrap();
+        assert_eq!(remainder, "");
+
+        let Statement::LocalFunction(function) = &ast.statements[0] else {
+            panic!("expected a local function");
+        };
+        assert_eq!(function.arguments.len(), 1);
+        assert_eq!(function.varargs, Some(Types::Number));
+        assert_eq!(
+            function.statements[1],
+            Statement::Return(vec![Expression::Call(Call {
+                function: Box::new(Expression::Name("select".to_string())),
+                method: None,
+                arguments: vec![
+                    Expression::Value(IdentifierValues::String(StringLiteral {
+                        value: b"#".to_vec(),
+                        delimiter: StringDelimiter::DoubleQuote
+                    })),
+                    Expression::Varargs
+                ]
+            })])
+        );
+
+        let IdentifierValues::Function(anonymous) = &ast.identifiers["g"].value else {
+            panic!("expected a function");
+        };
+        assert!(anonymous.arguments.is_empty());
+        assert_eq!(anonymous.varargs, Some(Types::Any));
+    }
+
+    #[test]
+    fn test_varargs_outside_vararg_function() {
+        let lines = "local function f(a)\n    if a then\n        print(...)\n    end\nend";
+        assert!(matches!(
+            make_ast(lines),
+            Err(nom::Err::Failure(nom::error::Error {
+                code: ErrorKind::Verify,
+                ..
+            }))
+        ));
+
+        // a nested vararg function has its own `...`
+        let lines = "local function f(a)\n    return function(...) return ... end\nend";
+        let (remainder, _) = make_ast(lines).unwrap();
+        assert_eq!(remainder, "");
+    }
+
+    #[test]
+    fn test_local_function() {
+        let lines = "local function fib(n)\n    local previous = fib(n - 1)\nend";
+        let (_, function) = parse_local_function(lines).unwrap();
+        assert_eq!(function.name, "fib");
+        assert!(function.identifiers.contains_key("previous"));
+        let IdentifierValues::Function(own) = &function.identifiers["fib"].value else {
+            panic!("expected the function to be in scope of its own body");
+        };
+        assert_eq!(own.arguments, function.arguments);
+
+        let (_, ast) = make_ast(lines).unwrap();
+        assert!(matches!(ast.statements[0], Statement::LocalFunction(_)));
+        assert!(ast.identifiers.contains_key("fib"));
+    }
+
+    #[test]
+    fn test_multiple_locals() {
+        let lines = "local a, b: number = 1, 2\nlocal x, y = f()\na, b = b, a\nlocal p, q = 1\n";
+        let (remainder, ast) = make_ast(lines).unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(
+            ast.statements[0],
+            Statement::Local(Local {
+                variables: vec![
+                    LocalVariable {
+                        name: "a".to_string(),
+                        type_annotation: None
+                    },
+                    LocalVariable {
+                        name: "b".to_string(),
+                        type_annotation: Some(Types::Number)
+                    }
+                ],
+                values: vec![
+                    Expression::Value(number("1")),
+                    Expression::Value(number("2"))
+                ]
+            })
+        );
+        let Statement::Local(destructure) = &ast.statements[1] else {
+            panic!("expected a local statement");
+        };
+        assert_eq!(destructure.variables.len(), 2);
+        assert!(matches!(destructure.values[..], [Expression::Call(_)]));
+        assert!(matches!(
+            &ast.identifiers["x"].value,
+            IdentifierValues::Expression(value) if matches!(**value, Expression::Call(_))
+        ));
+        assert_eq!(ast.identifiers["y"].value, IdentifierValues::Unknown);
+        assert_eq!(ast.identifiers["q"].value, IdentifierValues::Nil);
+        assert_eq!(
+            ast.statements[2],
+            Statement::Assignment(Assignment {
+                targets: vec![
+                    Expression::Name("a".to_string()),
+                    Expression::Name("b".to_string())
+                ],
+                values: vec![
+                    Expression::Name("b".to_string()),
+                    Expression::Name("a".to_string())
+                ]
+            })
+        );
+        assert_eq!(
+            ast.identifiers["a"].value,
+            IdentifierValues::Expression(Box::new(Expression::Name("b".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_statement_separators() {
+        let (remainder, ast) = make_ast("x = 1; y = 2\nlocal a = 1 ;\ndo end;\nf();").unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(ast.statements.len(), 5);
+        assert!(matches!(ast.statements[1], Statement::Assignment(_)));
+        assert!(matches!(ast.statements[3], Statement::Block(_)));
+        assert!(matches!(ast.statements[4], Statement::Call(_)));
+        assert_eq!(ast.identifiers["y"].value, number("2"));
+        assert_eq!(ast.identifiers["a"].value, number("1"));
+
+        let (remainder, ast) = make_ast(";\nlocal x = 1;;\ndo ; y = 2 ;; end ;").unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(ast.statements.len(), 2);
+        let Statement::Block(block) = &ast.statements[1] else {
+            panic!("expected a block");
+        };
+        assert_eq!(block.statements.len(), 1);
+
+        let (remainder, ast) = make_ast(";").unwrap();
+        assert_eq!(remainder, "");
+        assert!(ast.statements.is_empty());
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let (_, function) = parse_function("function f()\n    return a + 1, b\nend").unwrap();
+        assert_eq!(
+            function.statements,
+            vec![Statement::Return(vec![
+                Expression::Binary {
+                    left: Box::new(Expression::Name("a".to_string())),
+                    operator: BinaryOperator::Add,
+                    right: Box::new(Expression::Value(number("1")))
+                },
+                Expression::Name("b".to_string())
+            ])]
+        );
+
+        let (_, block) = parse_block("if done then return end").unwrap();
+        assert_eq!(block.statements, vec![Statement::Return(vec![])]);
+
+        let (_, function) = parse_function("function f() return function() end end").unwrap();
+        assert!(matches!(
+            &function.statements[..],
+            [Statement::Return(values)] if matches!(values[..], [Expression::Value(IdentifierValues::Function(_))])
+        ));
+
+        assert!(parse_block("do return 1 local x = 2 end").is_err());
+    }
+
+    #[test]
+    fn test_module_return() {
+        let module = "local module = {}\nreturn module\n";
+        let (remainder, ast) = make_ast(module).unwrap();
+        assert_eq!(remainder, "");
+        assert_eq!(
+            ast.statements.last(),
+            Some(&Statement::Return(vec![Expression::Name(
+                "module".to_string()
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_single_quoted_string() {
+        let (_, parsed_str) = parse_string::<()>("'say \"hi\"'").unwrap();
+        assert_eq!(
+            parsed_str,
+            StringLiteral {
+                value: b"say \"hi\"".to_vec(),
+                delimiter: StringDelimiter::SingleQuote
+            }
+        );
+    }
+
+    #[test]
+    fn test_long_bracket_string() {
+        let (_, parsed_str) = parse_string::<()>("[[\nmulti\nline]]").unwrap();
+        assert_eq!(
+            parsed_str,
+            StringLiteral {
+                value: b"multi\nline".to_vec(),
+                delimiter: StringDelimiter::LongBracket(0)
+            }
+        );
+
+        let (remainder, parsed_str) = parse_string::<()>("[==[ a ]] b ]=] c ]==] rest").unwrap();
+        assert_eq!(remainder, " rest");
+        assert_eq!(
+            parsed_str,
+            StringLiteral {
+                value: b" a ]] b ]=] c ".to_vec(),
+                delimiter: StringDelimiter::LongBracket(2)
+            }
+        );
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let (_, parsed_str) =
+            parse_string::<()>(r#"'\a\b\f\n\r\t\v\\\"\' \x41\66\0679 \u{E9}\u{1F600}'"#).unwrap();
+        assert_eq!(
+            parsed_str.value,
+            "\u{07}\u{08}\u{0C}\n\r\t\u{0B}\\\"' ABC9 \u{E9}\u{1F600}".as_bytes()
+        );
+
+        let (_, parsed_str) = parse_string::<()>("\"line\\\nbreak \\z  \n   skipped\"").unwrap();
+        assert_eq!(parsed_str.value, b"line\nbreak skipped");
+
+        let (_, parsed_str) = parse_string::<()>("\"\\xFF\\u{D800}\\u{10FFFF}\"").unwrap();
+        assert_eq!(
+            parsed_str.value,
+            [0xFF, 0xED, 0xA0, 0x80, 0xF4, 0x8F, 0xBF, 0xBF]
+        );
+    }
+
+    #[test]
+    fn test_invalid_string_escapes() {
+        for string in [
+            "\"\\/\"",
+            "\"\\q\"",
+            "\"\\256\"",
+            "\"\\x4\"",
+            "\"\\u{110000}\"",
+        ] {
+            assert!(
+                matches!(
+                    parse_string::<(&str, ErrorKind)>(string),
+                    Err(nom::Err::Failure((_, ErrorKind::Escaped)))
+                ),
+                "{string} should fail"
+            );
+        }
+    }
+
+    #[test]
+    fn test_interpolated_string() {
+        let (_, expression) =
+            parse_expression("`Hello {player.Name}, you have {coins} coins`").unwrap();
+        assert_eq!(
+            expression,
+            Expression::InterpolatedString(vec![
+                InterpolatedSegment::Literal(b"Hello ".to_vec()),
+                InterpolatedSegment::Expression(Expression::Field {
+                    table: Box::new(Expression::Name("player".to_string())),
+                    name: "Name".to_string()
+                }),
+                InterpolatedSegment::Literal(b", you have ".to_vec()),
+                InterpolatedSegment::Expression(Expression::Name("coins".to_string())),
+                InterpolatedSegment::Literal(b" coins".to_vec())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_interpolated_string_escapes() {
+        let (remainder, expression) =
+            parse_expression("`\\{literal} \\` {f(\"}\")}{`nested {x}`}` .. rest").unwrap();
+        assert_eq!(remainder, "");
+        let Expression::Binary { left, .. } = expression else {
+            panic!("expected a concatenation");
+        };
+        let Expression::InterpolatedString(segments) = *left else {
+            panic!("expected an interpolated string");
+        };
+        assert_eq!(segments.len(), 3);
+        assert_eq!(
+            segments[0],
+            InterpolatedSegment::Literal(b"{literal} ` ".to_vec())
+        );
+        assert!(matches!(
+            &segments[1],
+            InterpolatedSegment::Expression(Expression::Call(call))
+                if call.arguments == vec![Expression::Value(IdentifierValues::String(StringLiteral {
+                    value: b"}".to_vec(),
+                    delimiter: StringDelimiter::DoubleQuote
+                }))]
+        ));
+        assert!(matches!(
+            &segments[2],
+            InterpolatedSegment::Expression(Expression::InterpolatedString(nested)) if nested.len() == 2
+        ));
     }
 }
@@ -0,0 +1,407 @@
+// parses the statements that make up the body of a chunk, block or function
+
+use std::collections::HashMap;
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{map, opt, value, verify},
+    error::{Error, ErrorKind},
+    multi::{many0, many0_count, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+use crate::{
+    block_parser::{parse_block, Block},
+    comment_parser::{whitespace0, whitespace1},
+    expression_parser::{
+        parse_expression, parse_expression_list, parse_prefix_expression, BinaryOperator, Call,
+        Expression,
+    },
+    function_parser::{parse_function, parse_local_function, Function},
+    identifier_parser::{
+        expression_value, keyword, name_value, parse_equals, parse_name, Identifier,
+        IdentifierValues,
+    },
+    table_parser::{attach_function, attach_member, TableKey},
+    types::{parse_annotation, parse_type_alias, TypeAlias, Types},
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LocalVariable {
+    pub name: String,
+    pub type_annotation: Option<Types>,
+}
+
+/// `local a, b: number = 1, 2`. There can be more or fewer values than
+/// variables, as in `local x, y = f()`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Local {
+    pub variables: Vec<LocalVariable>,
+    pub values: Vec<Expression>,
+}
+
+/// `a, b.c = 1, 2`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Assignment {
+    pub targets: Vec<Expression>,
+    pub values: Vec<Expression>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CompoundAssignment {
+    pub target: Box<Expression>,
+    pub operator: BinaryOperator,
+    pub value: Box<Expression>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Statement {
+    Local(Local),
+    Assignment(Assignment),
+    CompoundAssignment(CompoundAssignment),
+    Call(Call),
+    Block(Block),
+    Function(Function),
+    LocalFunction(Function),
+    Break,
+    Return(Vec<Expression>),
+    TypeAlias(TypeAlias),
+}
+
+fn parse_compound_operator(input: &str) -> IResult<&str, BinaryOperator> {
+    alt((
+        value(BinaryOperator::Add, tag("+=")),
+        value(BinaryOperator::Subtract, tag("-=")),
+        value(BinaryOperator::Multiply, tag("*=")),
+        value(BinaryOperator::FloorDivide, tag("//=")),
+        value(BinaryOperator::Divide, tag("/=")),
+        value(BinaryOperator::Modulo, tag("%=")),
+        value(BinaryOperator::Power, tag("^=")),
+        value(BinaryOperator::Concat, tag("..=")),
+    ))(input)
+}
+
+/// Whether `target` is a name, field or index that can be assigned to.
+fn is_assignment_target(target: &Expression) -> bool {
+    matches!(
+        target,
+        Expression::Name(_) | Expression::Field { .. } | Expression::Index { .. }
+    )
+}
+
+fn parse_assignment_target(input: &str) -> IResult<&str, Expression> {
+    verify(parse_prefix_expression, is_assignment_target)(input)
+}
+
+fn target_name(target: &Expression) -> Option<&String> {
+    match target {
+        Expression::Name(name) => Some(name),
+        _ => None,
+    }
+}
+
+/// The name a value takes when assigned to `target`: `t` for `t = ...` and
+/// `name` for `t.name = ...`.
+fn value_name(target: &Expression) -> Option<&String> {
+    match target {
+        Expression::Name(name) | Expression::Field { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+/// Splits a target like `t.a.b[key]` into the variable `t`, the path of
+/// fields `a.b` and the key being written. Targets that go through anything
+/// other than names and fields, like `f().a`, have no fixed table.
+fn target_member(target: &Expression) -> Option<(&String, Vec<String>, TableKey)> {
+    let (table, key) = match target {
+        Expression::Field { table, name } => (table, TableKey::Name(name.clone())),
+        Expression::Index { table, key } => (table, TableKey::Expression(*key.clone())),
+        _ => return None,
+    };
+
+    let mut path = Vec::new();
+    let mut table = table.as_ref();
+    loop {
+        match table {
+            Expression::Name(root) => {
+                path.reverse();
+                return Some((root, path, key));
+            }
+            Expression::Field { table: inner, name } => {
+                path.push(name.clone());
+                table = inner;
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Gives tables and functions the name of the variable they are assigned to.
+fn name_values<'a>(names: impl Iterator<Item = Option<&'a String>>, values: &mut [Expression]) {
+    for (name, expression) in names.zip(values.iter_mut()) {
+        if let (Some(name), Expression::Value(value)) = (name, expression) {
+            name_value(value, name);
+        }
+    }
+}
+
+pub fn parse_local_variable(input: &str) -> IResult<&str, LocalVariable> {
+    let (remainder, (name, type_annotation)) = pair(parse_name, opt(parse_annotation))(input)?;
+
+    Ok((
+        remainder,
+        LocalVariable {
+            name: name.to_string(),
+            type_annotation,
+        },
+    ))
+}
+
+pub fn parse_local_declaration(input: &str) -> IResult<&str, Local> {
+    let (remainder, (_, variables, values)) = tuple((
+        pair(keyword("local"), whitespace1),
+        separated_list1(
+            tuple((whitespace0, tag(","), whitespace0)),
+            parse_local_variable,
+        ),
+        opt(preceded(parse_equals, parse_expression_list)),
+    ))(input)?;
+
+    let mut values = values.unwrap_or_default();
+    name_values(
+        variables.iter().map(|variable| Some(&variable.name)),
+        &mut values,
+    );
+
+    Ok((remainder, Local { variables, values }))
+}
+
+/// Parses the rest of an assignment after its first target.
+fn parse_assignment_rest(first: Expression, input: &str) -> IResult<&str, Assignment> {
+    let (remainder, (rest, _, mut values)) = tuple((
+        many0(preceded(
+            tuple((whitespace0, tag(","), whitespace0)),
+            parse_assignment_target,
+        )),
+        parse_equals,
+        parse_expression_list,
+    ))(input)?;
+
+    let targets = [vec![first], rest].concat();
+    name_values(targets.iter().map(value_name), &mut values);
+
+    Ok((remainder, Assignment { targets, values }))
+}
+
+/// Parses the operator and value of a compound assignment to `target`.
+fn parse_compound_rest(target: Expression, input: &str) -> IResult<&str, CompoundAssignment> {
+    let (remainder, (operator, value)) = pair(
+        delimited(whitespace0, parse_compound_operator, whitespace0),
+        parse_expression,
+    )(input)?;
+
+    Ok((
+        remainder,
+        CompoundAssignment {
+            target: Box::new(target),
+            operator,
+            value: Box::new(value),
+        },
+    ))
+}
+
+pub fn parse_assignment(input: &str) -> IResult<&str, Assignment> {
+    let (remainder, first) = parse_assignment_target(input)?;
+    parse_assignment_rest(first, remainder)
+}
+
+pub fn parse_compound_assignment(input: &str) -> IResult<&str, CompoundAssignment> {
+    let (remainder, target) = parse_assignment_target(input)?;
+    parse_compound_rest(target, remainder)
+}
+
+/// Parses an assignment, compound assignment or call. They all start with a
+/// prefix expression, which is parsed only once: parsing it again for each
+/// kind of statement takes exponential time in nested callbacks.
+fn parse_expression_statement(input: &str) -> IResult<&str, Statement> {
+    let (remainder, first) = parse_prefix_expression(input)?;
+
+    if !is_assignment_target(&first) {
+        return match first {
+            Expression::Call(call) => Ok((remainder, Statement::Call(call))),
+            _ => Err(nom::Err::Error(Error::new(input, ErrorKind::Verify))),
+        };
+    }
+
+    let statement = alt((
+        map(
+            |input| parse_compound_rest(first.clone(), input),
+            Statement::CompoundAssignment,
+        ),
+        map(
+            |input| parse_assignment_rest(first.clone(), input),
+            Statement::Assignment,
+        ),
+    ))(remainder);
+    statement
+}
+
+pub fn parse_statement(input: &str) -> IResult<&str, Statement> {
+    alt((
+        map(
+            terminated(parse_type_alias, whitespace0),
+            Statement::TypeAlias,
+        ),
+        terminated(parse_expression_statement, whitespace0),
+        map(parse_block, Statement::Block),
+        value(Statement::Break, terminated(keyword("break"), whitespace0)),
+        map(
+            terminated(parse_local_function, whitespace0),
+            Statement::LocalFunction,
+        ),
+        map(terminated(parse_function, whitespace0), Statement::Function),
+        map(
+            terminated(parse_local_declaration, whitespace0),
+            Statement::Local,
+        ),
+    ))(input)
+}
+
+/// Parses `return` with any number of values, including none.
+pub fn parse_return(input: &str) -> IResult<&str, Vec<Expression>> {
+    let (remainder, (_, values, _)) = tuple((
+        pair(keyword("return"), whitespace0),
+        opt(parse_expression_list),
+        tuple((whitespace0, opt(char(';')), whitespace0)),
+    ))(input)?;
+
+    Ok((remainder, values.unwrap_or_default()))
+}
+
+/// Parses the statements of a block. A return can only be the last statement,
+/// so anything after it is left for the caller, which then fails to find the
+/// end of the block. Any number of `;` may stand as empty statements.
+pub fn parse_statements(input: &str) -> IResult<&str, Vec<Statement>> {
+    let empty_statements = || many0_count(pair(char(';'), whitespace0));
+    let (remainder, (mut statements, return_values)) = pair(
+        preceded(
+            empty_statements(),
+            many0(terminated(parse_statement, empty_statements())),
+        ),
+        opt(parse_return),
+    )(input)?;
+
+    if let Some(values) = return_values {
+        statements.push(Statement::Return(values));
+    }
+
+    Ok((remainder, statements))
+}
+
+/// Pairs each name with the value at the same position. When the last value
+/// is a call or `...`, the names after it take its extra results, which are
+/// only known at runtime, as in `local x, y = f()`. Other names without a
+/// value are nil.
+fn pair_values<'a>(
+    names: impl Iterator<Item = Option<&'a String>>,
+    values: &[Expression],
+) -> Vec<Identifier> {
+    let missing = match values.last() {
+        Some(Expression::Call(_) | Expression::Varargs) => IdentifierValues::Unknown,
+        _ => IdentifierValues::Nil,
+    };
+
+    names
+        .enumerate()
+        .filter_map(|(index, name)| {
+            Some(Identifier {
+                name: name?.clone(),
+                value: values
+                    .get(index)
+                    .map_or(missing.clone(), |value| expression_value(value.clone())),
+                type_annotation: None,
+            })
+        })
+        .collect()
+}
+
+/// Collects the identifiers declared by a list of statements, keyed by name.
+pub fn statement_identifiers(statements: &[Statement]) -> HashMap<String, Identifier> {
+    let mut identifiers = HashMap::new();
+
+    for statement in statements {
+        let declared = match statement {
+            Statement::Local(local) => {
+                let mut declared = pair_values(
+                    local.variables.iter().map(|variable| Some(&variable.name)),
+                    &local.values,
+                );
+                for (identifier, variable) in declared.iter_mut().zip(&local.variables) {
+                    identifier.type_annotation = variable.type_annotation.clone();
+                }
+                declared
+            }
+            Statement::Assignment(assignment) => {
+                // `t.name = value` adds to a table that is already declared
+                for (index, target) in assignment.targets.iter().enumerate() {
+                    let Some((root, path, key)) = target_member(target) else {
+                        continue;
+                    };
+                    if let Some(Identifier {
+                        value: IdentifierValues::Table(table),
+                        ..
+                    }) = identifiers.get_mut(root)
+                    {
+                        let value = assignment
+                            .values
+                            .get(index)
+                            .map_or(IdentifierValues::Nil, |value| {
+                                expression_value(value.clone())
+                            });
+                        attach_member(table, &path, key, value);
+                    }
+                }
+
+                let mut assigned = pair_values(
+                    assignment.targets.iter().map(target_name),
+                    &assignment.values,
+                );
+                // assigning a new value doesn't change the declared type
+                for identifier in assigned.iter_mut() {
+                    if let Some(declared) = identifiers.get(&identifier.name) {
+                        identifier.type_annotation = declared.type_annotation.clone();
+                    }
+                }
+                assigned
+            }
+            // `function tab.name()` adds to a table rather than declaring a name
+            Statement::Function(function) if !function.path.is_empty() => {
+                if let Some(Identifier {
+                    value: IdentifierValues::Table(table),
+                    ..
+                }) = identifiers.get_mut(&function.path[0])
+                {
+                    attach_function(table, &function.path[1..], function.clone());
+                }
+                continue;
+            }
+            Statement::Function(function) | Statement::LocalFunction(function) => {
+                vec![Identifier {
+                    name: function.name.clone(),
+                    value: IdentifierValues::Function(Box::new(function.clone())),
+                    type_annotation: None,
+                }]
+            }
+            _ => continue,
+        };
+
+        for identifier in declared {
+            identifiers.insert(identifier.name.clone(), identifier);
+        }
+    }
+
+    identifiers
+}
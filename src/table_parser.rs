@@ -1,13 +1,16 @@
 use nom::{
-    bytes::complete::tag,
-    character::complete::multispace0,
+    branch::alt,
+    character::complete::{char, one_of},
     combinator::{map, opt},
-    multi::many0,
-    sequence::{delimited, tuple},
+    multi::separated_list1,
+    sequence::{delimited, pair, preceded, terminated},
     IResult,
 };
 
 use crate::{
+    comment_parser::whitespace0,
+    datatype_parsers::number_parser::NumberLiteral,
+    expression_parser::{parse_index, Expression},
     function_parser::Function,
     identifier_parser::{parse_equals, parse_identifier_value, parse_name, IdentifierValues},
 };
@@ -18,9 +21,17 @@ pub enum TableMemberType {
     Function(Function),
     Method(Function),
 }
+/// How a member is keyed: `name = value`, a positional `value` counted from 1,
+/// or `[expression] = value`.
+#[derive(PartialEq, Debug, Clone)]
+pub enum TableKey {
+    Name(String),
+    Index(usize),
+    Expression(Expression),
+}
 #[derive(PartialEq, Debug, Clone)]
 pub struct TableMember {
-    pub name: String,
+    pub key: TableKey,
     pub is_a: TableMemberType,
 }
 #[derive(PartialEq, Debug, Clone)]
@@ -29,40 +40,168 @@ pub struct Table {
     pub members: Vec<TableMember>,
 }
 
-fn make_table_member(name: &str, value: IdentifierValues) -> TableMember {
+impl TableMember {
+    /// The name of a `name = value` member.
+    pub fn name(&self) -> Option<&str> {
+        match &self.key {
+            TableKey::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+fn make_table_member(key: TableKey, value: IdentifierValues) -> TableMember {
     TableMember {
-        name: name.to_string(),
+        key,
         is_a: match value {
             IdentifierValues::Table(t) => TableMemberType::NestedTable(t),
+            IdentifierValues::Function(f) => TableMemberType::Function(*f),
             _ => TableMemberType::RawType(value),
         },
     }
 }
 
-fn parse_table_member(input: &str) -> IResult<&str, (&str, &str, IdentifierValues, Option<&str>)> {
-    tuple((
-        parse_name,
-        parse_equals,
-        parse_identifier_value,
-        opt(tag(",")),
+/// Follows `path` through nested tables.
+fn find_table<'a>(table: &'a mut Table, path: &[String]) -> Option<&'a mut Table> {
+    let Some((next, rest)) = path.split_first() else {
+        return Some(table);
+    };
+
+    let nested =
+        table
+            .members
+            .iter_mut()
+            .find_map(|member| match (&member.key, &mut member.is_a) {
+                (TableKey::Name(name), TableMemberType::NestedTable(nested)) if name == next => {
+                    Some(nested)
+                }
+                _ => None,
+            })?;
+
+    find_table(nested, rest)
+}
+
+/// The key a literal index stands for: `["x"]` is the same key as `x`, and
+/// `[1]` the same as the first positional value.
+fn literal_key(key: &TableKey) -> TableKey {
+    match key {
+        TableKey::Expression(Expression::Value(IdentifierValues::String(literal))) => {
+            match String::from_utf8(literal.value.clone()) {
+                Ok(name) => TableKey::Name(name),
+                Err(_) => key.clone(),
+            }
+        }
+        TableKey::Expression(Expression::Value(IdentifierValues::Number(NumberLiteral {
+            integer: Some(index),
+            ..
+        }))) if *index >= 1 => TableKey::Index(*index as usize),
+        _ => key.clone(),
+    }
+}
+
+/// Adds `member` to the table found by following `path` through nested
+/// tables, replacing the value of any member with the same key. Returns false
+/// when there is no such table.
+fn set_member(table: &mut Table, path: &[String], member: TableMember) -> bool {
+    let Some(table) = find_table(table, path) else {
+        return false;
+    };
+
+    let key = literal_key(&member.key);
+    match table
+        .members
+        .iter_mut()
+        .find(|existing| literal_key(&existing.key) == key)
+    {
+        Some(existing) => existing.is_a = member.is_a,
+        None => table.members.push(member),
+    }
+    true
+}
+
+/// Stores `function` as a member of the table found by following `path`
+/// through nested tables. Returns false when there is no such table.
+pub fn attach_function(table: &mut Table, path: &[String], function: Function) -> bool {
+    let member = TableMember {
+        key: TableKey::Name(function.name.clone()),
+        is_a: if function.is_method {
+            TableMemberType::Method(function)
+        } else {
+            TableMemberType::Function(function)
+        },
+    };
+
+    set_member(table, path, member)
+}
+
+/// Stores a value written with `t.path.key = value` or `t.path[key] = value`.
+/// Returns false when there is no table at `path`.
+pub fn attach_member(
+    table: &mut Table,
+    path: &[String],
+    key: TableKey,
+    value: IdentifierValues,
+) -> bool {
+    set_member(table, path, make_table_member(key, value))
+}
+
+/// A key and value without a position yet, which is only known once every
+/// field of the table is parsed.
+enum TableField {
+    Keyed(TableKey, IdentifierValues),
+    Positional(IdentifierValues),
+}
+
+fn parse_table_field(input: &str) -> IResult<&str, TableField> {
+    alt((
+        map(
+            pair(
+                terminated(parse_index, parse_equals),
+                parse_identifier_value,
+            ),
+            |(key, value)| TableField::Keyed(TableKey::Expression(key), value),
+        ),
+        map(
+            pair(terminated(parse_name, parse_equals), parse_identifier_value),
+            |(name, value)| TableField::Keyed(TableKey::Name(name.to_string()), value),
+        ),
+        map(parse_identifier_value, TableField::Positional),
     ))(input)
 }
 
+/// Parses a table constructor. Fields are separated by `,` or `;`, which may
+/// also follow the last field.
 pub fn parse_table(input: &str) -> IResult<&str, Table> {
-    let (remainder, values) = delimited(
-        tag("{"),
-        many0(map(
-            delimited(multispace0, parse_table_member, multispace0),
-            |(s, _, i, _)| make_table_member(s, i),
+    let (remainder, fields) = delimited(
+        pair(char('{'), whitespace0),
+        opt(terminated(
+            separated_list1(
+                delimited(whitespace0, one_of(",;"), whitespace0),
+                parse_table_field,
+            ),
+            opt(preceded(whitespace0, one_of(",;"))),
         )),
-        tag("}"),
+        pair(whitespace0, char('}')),
     )(input)?;
 
+    let mut index = 0;
+    let members = fields
+        .unwrap_or_default()
+        .into_iter()
+        .map(|field| match field {
+            TableField::Keyed(key, value) => make_table_member(key, value),
+            TableField::Positional(value) => {
+                index += 1;
+                make_table_member(TableKey::Index(index), value)
+            }
+        })
+        .collect();
+
     Ok((
         remainder,
         Table {
             name: String::new(),
-            members: values,
+            members,
         },
     ))
 }
@@ -1,5 +1,23 @@
 use std::str::FromStr;
 
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{char, one_of},
+    combinator::{map, map_opt, opt, recognize, value},
+    error::{Error, ErrorKind},
+    multi::{fold_many0, many1, separated_list0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+use crate::{
+    comment_parser::{whitespace0, whitespace1},
+    datatype_parsers::{boolean_parser::parse_boolean, string_parser::parse_string},
+    expression_parser::{parse_expression, Expression},
+    identifier_parser::{keyword, parse_name},
+};
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Types {
     Any,
@@ -8,6 +26,78 @@ pub enum Types {
     Boolean,
     Table,
     Function,
+    Nil,
+    /// A singleton type that only holds one string, like `"a"` in
+    /// `"a" | "b"`.
+    StringSingleton(Vec<u8>),
+    /// `true` or `false` as a type.
+    BooleanSingleton(bool),
+    /// `typeof(expression)`, the type of an expression.
+    Typeof(Box<Expression>),
+    /// A type referred to by name, such as `Player`, `Array<T>` or
+    /// `Module.Type`.
+    Reference {
+        name: String,
+        generics: Vec<Types>,
+    },
+    /// `T?`
+    Optional(Box<Types>),
+    /// `A | B`
+    Union(Vec<Types>),
+    /// `A & B`
+    Intersection(Vec<Types>),
+    /// `{ name: T, [K]: V }`. An array type `{T}` has a `number` indexer.
+    TableType {
+        properties: Vec<TypeProperty>,
+        indexer: Option<Box<TypeIndexer>>,
+    },
+    /// A generic type pack such as `T...`, which stands for any number of
+    /// types.
+    GenericPack(String),
+    /// `...T`, any number of values of type `T`.
+    Variadic(Box<Types>),
+    /// `<T>(A, B) -> R`. Argument names, as in `(a: A) -> R`, are not kept.
+    FunctionType {
+        generics: Vec<GenericParameter>,
+        arguments: Vec<Types>,
+        returns: TypePack,
+    },
+}
+
+/// The types returned by a function, as in `(number, string)`. `tail` holds a
+/// `Types::Variadic` or `Types::GenericPack` that stands for any number of
+/// values after `types`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypePack {
+    pub types: Vec<Types>,
+    pub tail: Option<Box<Types>>,
+}
+
+impl From<Vec<Types>> for TypePack {
+    /// Splits a variadic type or generic pack at the end of `types` into the
+    /// tail.
+    fn from(mut types: Vec<Types>) -> Self {
+        let tail = match types.last() {
+            Some(Types::Variadic(_) | Types::GenericPack(_)) => types.pop().map(Box::new),
+            _ => None,
+        };
+
+        TypePack { types, tail }
+    }
+}
+
+/// `name: T` in a table type.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeProperty {
+    pub name: String,
+    pub property_type: Types,
+}
+
+/// `[K]: V` in a table type.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeIndexer {
+    pub key: Types,
+    pub value: Types,
 }
 
 impl FromStr for Types {
@@ -21,9 +111,373 @@ impl FromStr for Types {
             "boolean" => Ok(Self::Boolean),
             "table" => Ok(Self::Table),
             "function" => Ok(Self::Function),
-            "()->()" => Ok(Self::Function),
-            "() -> ()" => Ok(Self::Function),
+            "nil" => Ok(Self::Nil),
             _ => Err(()),
         }
     }
 }
+
+/// A generic parameter, like `T` or `U = string` in
+/// `type Map<T, U = string> = ...`. `is_pack` is set for a type pack
+/// parameter such as `T...`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GenericParameter {
+    pub name: String,
+    pub is_pack: bool,
+    pub default: Option<Types>,
+}
+
+/// `type Name<T> = Type`, or `export type Name<T> = Type` when `exported`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeAlias {
+    pub name: String,
+    pub generics: Vec<GenericParameter>,
+    pub exported: bool,
+    pub alias_type: Types,
+}
+
+/// A field of a table type, before the fields are sorted into properties and
+/// an indexer.
+enum TableTypeField {
+    Property(TypeProperty),
+    Indexer(TypeIndexer),
+    Positional(Types),
+}
+
+fn type_separator<'a>() -> impl FnMut(&'a str) -> IResult<&'a str, char> {
+    delimited(whitespace0, char(','), whitespace0)
+}
+
+/// Parses `Name`, `Module.Name` or `Name<A, B>`.
+fn parse_type_reference(input: &str) -> IResult<&str, Types> {
+    let (remainder, (name, generics)) = pair(
+        recognize(separated_list1(char('.'), parse_name)),
+        opt(delimited(
+            pair(char('<'), whitespace0),
+            separated_list1(type_separator(), parse_type),
+            pair(whitespace0, char('>')),
+        )),
+    )(input)?;
+
+    let reference = match generics {
+        Some(generics) => Types::Reference {
+            name: name.to_string(),
+            generics,
+        },
+        None => Types::from_str(name).unwrap_or_else(|_| Types::Reference {
+            name: name.to_string(),
+            generics: Vec::new(),
+        }),
+    };
+
+    Ok((remainder, reference))
+}
+
+fn parse_table_type_field(input: &str) -> IResult<&str, TableTypeField> {
+    let colon = || tuple((whitespace0, char(':'), whitespace0));
+
+    alt((
+        map(
+            pair(
+                delimited(
+                    pair(char('['), whitespace0),
+                    parse_type,
+                    pair(whitespace0, char(']')),
+                ),
+                preceded(colon(), parse_type),
+            ),
+            // `["name"]: T` is a property that needn't be a valid name
+            |(key, value)| match key {
+                Types::StringSingleton(name) => match String::from_utf8(name) {
+                    Ok(name) => TableTypeField::Property(TypeProperty {
+                        name,
+                        property_type: value,
+                    }),
+                    Err(error) => TableTypeField::Indexer(TypeIndexer {
+                        key: Types::StringSingleton(error.into_bytes()),
+                        value,
+                    }),
+                },
+                key => TableTypeField::Indexer(TypeIndexer { key, value }),
+            },
+        ),
+        map(
+            pair(parse_name, preceded(colon(), parse_type)),
+            |(name, property_type)| {
+                TableTypeField::Property(TypeProperty {
+                    name: name.to_string(),
+                    property_type,
+                })
+            },
+        ),
+        map(parse_type, TableTypeField::Positional),
+    ))(input)
+}
+
+/// Parses `{T}`, or a table type with properties and at most one indexer.
+fn parse_table_type(input: &str) -> IResult<&str, Types> {
+    map_opt(
+        delimited(
+            pair(char('{'), whitespace0),
+            terminated(
+                separated_list0(
+                    delimited(whitespace0, one_of(",;"), whitespace0),
+                    parse_table_type_field,
+                ),
+                opt(preceded(whitespace0, one_of(",;"))),
+            ),
+            pair(whitespace0, char('}')),
+        ),
+        |fields| {
+            let mut properties = Vec::new();
+            let mut indexer = None;
+            let is_array = fields.len() == 1;
+
+            for field in fields {
+                match field {
+                    TableTypeField::Property(property) => properties.push(property),
+                    TableTypeField::Indexer(_) if indexer.is_some() => return None,
+                    TableTypeField::Indexer(field) => indexer = Some(Box::new(field)),
+                    TableTypeField::Positional(value) if is_array => {
+                        indexer = Some(Box::new(TypeIndexer {
+                            key: Types::Number,
+                            value,
+                        }))
+                    }
+                    TableTypeField::Positional(_) => return None,
+                }
+            }
+
+            Some(Types::TableType {
+                properties,
+                indexer,
+            })
+        },
+    )(input)
+}
+
+/// Parses the argument of a function type, which may be named as in `a: A`,
+/// along with whether it was named.
+fn parse_function_type_argument(input: &str) -> IResult<&str, (bool, Types)> {
+    pair(
+        map(
+            opt(pair(
+                parse_name,
+                tuple((whitespace0, char(':'), whitespace0)),
+            )),
+            |name| name.is_some(),
+        ),
+        parse_type,
+    )(input)
+}
+
+/// Parses `(A, b: B)`, which may turn out to be the arguments of a function
+/// type, a type in parentheses or a type pack.
+fn parse_type_list(input: &str) -> IResult<&str, Vec<(bool, Types)>> {
+    delimited(
+        pair(char('('), whitespace0),
+        separated_list0(type_separator(), parse_function_type_argument),
+        pair(whitespace0, char(')')),
+    )(input)
+}
+
+fn parse_function_returns(input: &str) -> IResult<&str, TypePack> {
+    preceded(
+        tuple((whitespace0, tag("->"), whitespace0)),
+        parse_type_pack,
+    )(input)
+}
+
+fn unnamed(list: Vec<(bool, Types)>) -> Vec<Types> {
+    list.into_iter().map(|(_, list_type)| list_type).collect()
+}
+
+/// Parses the types a function returns: `R`, `()`, `(A, B)`, `...R`, `R...`
+/// or `(A, ...R)`.
+pub fn parse_type_pack(input: &str) -> IResult<&str, TypePack> {
+    let (remainder, list) = match parse_type_list(input) {
+        Ok(parsed) => parsed,
+        Err(nom::Err::Error(_)) => {
+            return map(parse_type, |return_type| TypePack::from(vec![return_type]))(input)
+        }
+        Err(error) => return Err(error),
+    };
+
+    // The list is parsed only once, as parsing it again as a type takes
+    // exponential time in nested function types. It is the pack itself unless
+    // it starts a type such as `(A) -> B` or `(A)?`.
+    let (remainder, returns) = opt(parse_function_returns)(remainder)?;
+    let first = match returns {
+        Some(returns) => Types::FunctionType {
+            generics: Vec::new(),
+            arguments: unnamed(list),
+            returns,
+        },
+        None if list.iter().any(|(named, _)| *named) => {
+            return Err(nom::Err::Error(Error::new(input, ErrorKind::Verify)))
+        }
+        None if list.len() != 1 => return Ok((remainder, TypePack::from(unnamed(list)))),
+        None => unnamed(list).remove(0),
+    };
+
+    let (remainder, first) = parse_optional(first, remainder)?;
+    let (remainder, return_type) = parse_type_operators(first, remainder)?;
+
+    Ok((remainder, TypePack::from(vec![return_type])))
+}
+
+/// Parses `<T>(A, B) -> R`, or `(T)`, which is just `T`.
+fn parse_parenthesized_type(input: &str) -> IResult<&str, Types> {
+    let (remainder, (generics, arguments)) = pair(
+        opt(terminated(parse_generic_parameters, whitespace0)),
+        parse_type_list,
+    )(input)?;
+
+    match opt(parse_function_returns)(remainder)? {
+        (remainder, Some(returns)) => Ok((
+            remainder,
+            Types::FunctionType {
+                generics: generics.unwrap_or_default(),
+                arguments: unnamed(arguments),
+                returns,
+            },
+        )),
+        (_, None) if generics.is_none() && matches!(arguments[..], [(false, _)]) => {
+            Ok((remainder, unnamed(arguments).remove(0)))
+        }
+        (_, None) => Err(nom::Err::Error(Error::new(input, ErrorKind::Verify))),
+    }
+}
+
+fn parse_simple_type(input: &str) -> IResult<&str, Types> {
+    let (remainder, simple_type) = alt((
+        value(Types::Nil, keyword("nil")),
+        value(Types::Function, keyword("function")),
+        map(parse_string, |literal| {
+            Types::StringSingleton(literal.value)
+        }),
+        map(parse_boolean, Types::BooleanSingleton),
+        map(
+            preceded(
+                pair(keyword("typeof"), whitespace0),
+                delimited(
+                    pair(char('('), whitespace0),
+                    parse_expression,
+                    pair(whitespace0, char(')')),
+                ),
+            ),
+            |expression| Types::Typeof(Box::new(expression)),
+        ),
+        parse_table_type,
+        parse_parenthesized_type,
+        map(terminated(parse_name, tag("...")), |name| {
+            Types::GenericPack(name.to_string())
+        }),
+        map(
+            preceded(pair(tag("..."), whitespace0), parse_simple_type),
+            |variadic| Types::Variadic(Box::new(variadic)),
+        ),
+        parse_type_reference,
+    ))(input)?;
+
+    parse_optional(simple_type, remainder)
+}
+
+/// Parses any `?` after `simple_type`.
+fn parse_optional(simple_type: Types, input: &str) -> IResult<&str, Types> {
+    fold_many0(
+        preceded(whitespace0, char('?')),
+        move || simple_type.clone(),
+        |optional, _| Types::Optional(Box::new(optional)),
+    )(input)
+}
+
+/// Parses a type. Unions and intersections can't be mixed without
+/// parentheses.
+pub fn parse_type(input: &str) -> IResult<&str, Types> {
+    let (remainder, first) = parse_simple_type(input)?;
+    parse_type_operators(first, remainder)
+}
+
+/// Parses the rest of a union or intersection that starts with `first`.
+fn parse_type_operators(first: Types, remainder: &str) -> IResult<&str, Types> {
+    let operand = |operator| {
+        preceded(
+            delimited(whitespace0, char(operator), whitespace0),
+            parse_simple_type,
+        )
+    };
+
+    if let Ok((remainder, rest)) = many1(operand('|'))(remainder) {
+        return Ok((remainder, Types::Union([vec![first], rest].concat())));
+    }
+    if let Ok((remainder, rest)) = many1(operand('&'))(remainder) {
+        return Ok((remainder, Types::Intersection([vec![first], rest].concat())));
+    }
+
+    Ok((remainder, first))
+}
+
+fn parse_generic_parameter(input: &str) -> IResult<&str, GenericParameter> {
+    let (remainder, (name, pack, default)) = tuple((
+        parse_name,
+        opt(tag("...")),
+        opt(preceded(
+            tuple((whitespace0, char('='), whitespace0)),
+            parse_type,
+        )),
+    ))(input)?;
+
+    Ok((
+        remainder,
+        GenericParameter {
+            name: name.to_string(),
+            is_pack: pack.is_some(),
+            default,
+        },
+    ))
+}
+
+/// Parses `<T, U = default, V...>`.
+pub fn parse_generic_parameters(input: &str) -> IResult<&str, Vec<GenericParameter>> {
+    delimited(
+        pair(char('<'), whitespace0),
+        separated_list1(type_separator(), parse_generic_parameter),
+        pair(whitespace0, char('>')),
+    )(input)
+}
+
+/// Parses `type Name<T> = Type`, optionally preceded by `export`.
+pub fn parse_type_alias(input: &str) -> IResult<&str, TypeAlias> {
+    let (remainder, (export, _, name, generics, _, alias_type)) = tuple((
+        opt(pair(keyword("export"), whitespace1)),
+        pair(keyword("type"), whitespace1),
+        parse_name,
+        opt(preceded(whitespace0, parse_generic_parameters)),
+        tuple((whitespace0, char('='), whitespace0)),
+        parse_type,
+    ))(input)?;
+
+    Ok((
+        remainder,
+        TypeAlias {
+            name: name.to_string(),
+            generics: generics.unwrap_or_default(),
+            exported: export.is_some(),
+            alias_type,
+        },
+    ))
+}
+
+/// Parses the return annotation of a function, such as `: (number, string)`.
+pub fn parse_return_annotation(input: &str) -> IResult<&str, TypePack> {
+    preceded(
+        tuple((whitespace0, char(':'), whitespace0)),
+        parse_type_pack,
+    )(input)
+}
+
+/// Parses a type annotation, such as `: number`.
+pub fn parse_annotation(input: &str) -> IResult<&str, Types> {
+    preceded(tuple((whitespace0, char(':'), whitespace0)), parse_type)(input)
+}
@@ -0,0 +1,16 @@
+local tab = {}
+local nested = {
+    inner = {}
+}
+
+function tab.thing(one, two)
+end
+
+function tab:method(one)
+end
+
+function nested.inner:deep(one)
+end
+
+function other.thing(one)
+end
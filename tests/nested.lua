@@ -0,0 +1,16 @@
+function send(amount)
+    local sender = "tom"
+    if amount > 10 then
+        for i = 1, amount do
+            local ending = i
+            if ending == 5 then
+                break
+            end
+        end
+    else
+        while amount < 10 do
+            amount += 1
+        end
+    end
+    local after = true
+end